@@ -29,6 +29,14 @@ else
 	@$(REPO_BIN) push-list $(COOKBOOK_OPTS)
 endif
 
+# Check all recipes for mistakes in recipe.toml
+repo-lint: $(FSTOOLS_TAG) FORCE
+ifeq ($(PODMAN_BUILD),1)
+	$(PODMAN_RUN) make $@
+else
+	$(REPO_BIN) lint --all
+endif
+
 # Clean specific target to all recipes, similar to repo_clean but more specific
 repo_clean_target: $(FSTOOLS_TAG) FORCE
 ifeq ($(PODMAN_BUILD),1)
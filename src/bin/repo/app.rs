@@ -4,7 +4,7 @@ use crate::*;
 use cookbook::cook::fetch::FetchResult;
 use cookbook::cook::pty::{UnixSlavePty, flush_pty, setup_pty, write_to_pty};
 use cookbook::cook::tui::{drain_buffer_to_lines, join_logs, kill_everything, render_build_log};
-use cookbook::recipe::CookRecipe;
+use cookbook::recipe::{CookRecipe, SourceRecipe};
 use cookbook::{Error, Result, staged_pkg};
 use pkg::PackageName;
 use ratatui::Terminal;
@@ -14,11 +14,11 @@ use ratatui::style::{Color, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io::{Read, Write, stdin, stdout};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::{Arc, mpsc};
+use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 use std::time::{Duration, Instant};
 use termion::event::{Event, Key};
@@ -217,19 +217,49 @@ impl TuiApp {
         Ok(())
     }
 
+    fn is_fetching(&self, recipe_name: &PackageName) -> bool {
+        self.recipes
+            .iter()
+            .any(|(r, s)| r.name == *recipe_name && *s == RecipeStatus::Fetching)
+    }
+
+    fn set_active_fetch(&mut self, recipe_name: Option<PackageName>) {
+        self.active_fetch = recipe_name;
+        self.log_scroll = 0;
+        self.auto_scroll = true;
+    }
+
     // Update the state based on a message from a worker thread
     pub fn update_status(&mut self, update: StatusUpdate) {
         let (name, new_status) = match update {
             StatusUpdate::StartFetch(name) => {
-                self.active_fetch = Some(name.clone());
+                // with parallel fetches, keep showing a log until that fetch is done
+                if !self
+                    .active_fetch
+                    .as_ref()
+                    .is_some_and(|active| self.is_fetching(active))
+                {
+                    self.set_active_fetch(Some(name.clone()));
+                }
                 self.logs.insert(name.clone(), Vec::new());
                 self.log_byte_buffer.insert(name.clone(), Vec::new());
-                self.log_scroll = 0;
-                self.auto_scroll = true;
                 (name.clone(), RecipeStatus::Fetching)
             }
-            StatusUpdate::Fetched(recipe) => (recipe.name.clone(), RecipeStatus::Fetched),
+            StatusUpdate::Fetched(recipe) => {
+                if self.active_fetch.as_ref() == Some(&recipe.name) {
+                    let next = self
+                        .recipes
+                        .iter()
+                        .find(|(r, s)| *s == RecipeStatus::Fetching && r.name != recipe.name)
+                        .map(|(r, _)| r.name.clone());
+                    if next.is_some() {
+                        self.set_active_fetch(next);
+                    }
+                }
+                (recipe.name.clone(), RecipeStatus::Fetched)
+            }
             StatusUpdate::FailFetch(recipe, err) => {
+                self.set_active_fetch(Some(recipe.name.clone()));
                 self.prompt = Some(FailurePrompt::new(recipe.clone(), err.clone()));
                 (recipe.name.clone(), RecipeStatus::Failed(err))
             }
@@ -290,18 +320,60 @@ impl TuiApp {
     }
 }
 
+/// Recipes waiting to be fetched, indexed by their cook order
+struct FetchQueue {
+    pending: VecDeque<(usize, CookRecipe)>,
+    /// source dirs being fetched, as recipes can share them
+    fetching: HashSet<PathBuf>,
+}
+
+/// Take the first pending recipe which source dir is not being fetched by another thread
+fn next_fetch(queue: &Mutex<FetchQueue>) -> Option<(usize, CookRecipe, PathBuf)> {
+    loop {
+        {
+            let mut queue = queue.lock().unwrap();
+            if queue.pending.is_empty() {
+                return None;
+            }
+            let position = queue
+                .pending
+                .iter()
+                .position(|(_, recipe)| !queue.fetching.contains(&fetch_source_key(recipe)));
+            if let Some(position) = position {
+                let (index, recipe) = queue.pending.remove(position).unwrap();
+                let source_key = fetch_source_key(&recipe);
+                queue.fetching.insert(source_key.clone());
+                return Some((index, recipe, source_key));
+            }
+        }
+        thread::sleep(PROMPT_WAIT);
+    }
+}
+
+/// The source dir written by fetching a recipe, "same_as" recipes write to their target
+fn fetch_source_key(recipe: &CookRecipe) -> PathBuf {
+    let dir = match &recipe.recipe.source {
+        Some(SourceRecipe::SameAs { same_as }) => recipe.dir.join(same_as),
+        _ => recipe.dir.clone(),
+    };
+    dir.canonicalize().unwrap_or(dir)
+}
+
 pub fn run_tui_cook(config: CliConfig, recipes: Vec<CookRecipe>) -> Result<TuiApp> {
     let (work_tx, work_rx) = mpsc::channel::<(CookRecipe, FetchResult)>();
     let (status_tx, status_rx) = mpsc::channel::<StatusUpdate>();
 
     let running = Arc::new(AtomicBool::new(true));
     let prompting = Arc::new(AtomicU32::new(0));
+    // set before "done" is released from prompting, so nobody prompts after it
+    let stopped = Arc::new(AtomicBool::new(false));
     const TICK_RATE: Duration = Duration::from_millis(100);
 
     // ---- Cooker Thread ----
     let cooker_config = config.clone();
     let cooker_status_tx = status_tx.clone();
     let cooker_prompting = prompting.clone();
+    let cooker_stopped = stopped.clone();
     let cooker_handle = thread::spawn(move || {
         'done: for (mut recipe, fetch_result) in work_rx {
             let name = recipe.name.clone();
@@ -373,6 +445,7 @@ pub fn run_tui_cook(config: CliConfig, recipes: Vec<CookRecipe>) -> Result<TuiAp
                                     break 'again;
                                 } // skip
                                 4 => {
+                                    cooker_stopped.store(true, Ordering::SeqCst);
                                     cooker_prompting.swap(0, Ordering::SeqCst);
                                     break 'done;
                                 } // done
@@ -407,87 +480,138 @@ pub fn run_tui_cook(config: CliConfig, recipes: Vec<CookRecipe>) -> Result<TuiAp
         }
     });
 
-    // ---- Fetcher Thread ----
-    let fetcher_recipes = recipes.clone();
-    let fetcher_status_tx = status_tx.clone();
-    let fetcher_config = config.clone();
-    let fetcher_prompting = prompting.clone();
-    let fetcher_handle = thread::spawn(move || {
-        'done: for mut recipe in fetcher_recipes {
-            let name = recipe.name.clone();
-            let (mut stdout_writer, mut stderr_writer) = setup_logger(&fetcher_status_tx, &name);
-            let mut logger = Some((&mut stdout_writer, &mut stderr_writer));
-            'again: loop {
-                fetcher_status_tx
-                    .send(StatusUpdate::StartFetch(name.clone()))
-                    .unwrap();
-                let _ = recipe.reload_recipe(); // reread recipe.toml in case we're retrying
-                let handler = handle_fetch(&recipe, &fetcher_config, true, &logger);
-                if let Some(log_path) = fetcher_config.logs_dir.as_ref()
-                    // prefer to retain full build logs
-                    && !matches!(handler, Ok(FetchResult { cached: true, .. }))
-                {
-                    if let Err(err_ctx) = &handler {
-                        write_to_pty(&logger, &format!("\n{err_ctx}"));
-                    }
-                    flush_pty(&mut logger);
-                    let log_path = log_path.join(format!("{}/{}.log", recipe.target, name.name()));
+    // ---- Fetcher Threads ----
+    // independent recipes are fetched in parallel, the orderer thread below
+    // then feeds them to the cooker in the original (dependency) order
+    let (fetched_tx, fetched_rx) = mpsc::channel::<(usize, Option<(CookRecipe, FetchResult)>)>();
+    let fetch_queue = Arc::new(Mutex::new(FetchQueue {
+        pending: recipes.iter().cloned().enumerate().collect(),
+        fetching: HashSet::new(),
+    }));
+    let fetch_jobs = config.cook.fetch_jobs.clamp(1, recipes.len().max(1));
+    let mut fetcher_handles = Vec::with_capacity(fetch_jobs);
+    for _ in 0..fetch_jobs {
+        let fetcher_queue = fetch_queue.clone();
+        let fetcher_tx = fetched_tx.clone();
+        let fetcher_status_tx = status_tx.clone();
+        let fetcher_config = config.clone();
+        let fetcher_prompting = prompting.clone();
+        let fetcher_stopped = stopped.clone();
+        fetcher_handles.push(thread::spawn(move || {
+            'done: while let Some((index, mut recipe, source_key)) = next_fetch(&fetcher_queue) {
+                let name = recipe.name.clone();
+                let (mut stdout_writer, mut stderr_writer) =
+                    setup_logger(&fetcher_status_tx, &name);
+                let mut logger = Some((&mut stdout_writer, &mut stderr_writer));
+                let fetched = 'again: loop {
                     fetcher_status_tx
-                        .send(StatusUpdate::FlushLog(name.clone(), log_path))
-                        .unwrap_or_default();
-                }
-                match handler {
-                    Ok(fetch) => {
-                        fetcher_status_tx
-                            .send(StatusUpdate::Fetched(recipe.clone()))
-                            .unwrap();
-                        if work_tx.send((recipe.clone(), fetch)).is_err() {
-                            // Cooker thread died
-                            break 'done;
-                        }
-                        if fetcher_config.cook.nonstop
-                            && fetcher_prompting.load(Ordering::SeqCst) == 4
-                        {
-                            break 'done;
+                        .send(StatusUpdate::StartFetch(name.clone()))
+                        .unwrap();
+                    let _ = recipe.reload_recipe(); // reread recipe.toml in case we're retrying
+                    let handler = handle_fetch(&recipe, &fetcher_config, true, &logger);
+                    if let Some(log_path) = fetcher_config.logs_dir.as_ref()
+                        // prefer to retain full build logs
+                        && !matches!(handler, Ok(FetchResult { cached: true, .. }))
+                    {
+                        if let Err(err_ctx) = &handler {
+                            write_to_pty(&logger, &format!("\n{err_ctx}"));
                         }
-                        break;
-                    }
-                    Err(e) => {
+                        flush_pty(&mut logger);
+                        let log_path =
+                            log_path.join(format!("{}/{}.log", recipe.target, name.name()));
                         fetcher_status_tx
-                            .send(StatusUpdate::FailFetch(recipe.clone(), e.to_string()))
+                            .send(StatusUpdate::FlushLog(name.clone(), log_path))
                             .unwrap_or_default();
-                        if fetcher_config.cook.nonstop {
-                            if fetcher_prompting.load(Ordering::SeqCst) == 4 {
-                                break 'done;
-                            }
-                            let _ = handle_nonstop_fail(&recipe);
-                            break;
-                        }
-                        while fetcher_prompting.load(Ordering::SeqCst) != 0 {
-                            thread::sleep(PROMPT_WAIT); // wait other prompt
+                    }
+                    match handler {
+                        Ok(fetch) => {
+                            fetcher_status_tx
+                                .send(StatusUpdate::Fetched(recipe.clone()))
+                                .unwrap();
+                            break Some((recipe.clone(), fetch));
                         }
-                        fetcher_prompting.swap(1, Ordering::SeqCst);
-                        'wait: loop {
-                            match fetcher_prompting.load(Ordering::SeqCst) {
-                                0 => break 'again,
-                                1 => thread::sleep(PROMPT_WAIT),
-                                2 => {
-                                    fetcher_prompting.swap(0, Ordering::SeqCst);
-                                    break 'wait;
-                                } // retry
-                                3 => {
-                                    fetcher_prompting.swap(0, Ordering::SeqCst);
-                                    let _ = handle_nonstop_fail(&recipe);
-                                    break 'again;
-                                } // skip
-                                4 => {
+                        Err(e) => {
+                            if !fetcher_config.cook.nonstop {
+                                // other fetchers may be failing at the same time,
+                                // so wait for their prompt before showing ours
+                                while fetcher_prompting
+                                    .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                                    .is_err()
+                                {
+                                    if fetcher_stopped.load(Ordering::SeqCst) {
+                                        break 'done;
+                                    }
+                                    thread::sleep(PROMPT_WAIT); // wait other prompt
+                                }
+                                // the prompt we waited for may have been "done"
+                                if fetcher_stopped.load(Ordering::SeqCst) {
                                     fetcher_prompting.swap(0, Ordering::SeqCst);
                                     break 'done;
-                                } // done
-                                _ => unreachable!(),
+                                }
+                            }
+                            fetcher_status_tx
+                                .send(StatusUpdate::FailFetch(recipe.clone(), e.to_string()))
+                                .unwrap_or_default();
+                            if fetcher_config.cook.nonstop {
+                                if fetcher_prompting.load(Ordering::SeqCst) == 4 {
+                                    break 'done;
+                                }
+                                let _ = handle_nonstop_fail(&recipe);
+                                break None;
+                            }
+                            'wait: loop {
+                                match fetcher_prompting.load(Ordering::SeqCst) {
+                                    0 => break 'again None,
+                                    1 => thread::sleep(PROMPT_WAIT),
+                                    2 => {
+                                        fetcher_prompting.swap(0, Ordering::SeqCst);
+                                        break 'wait;
+                                    } // retry
+                                    3 => {
+                                        fetcher_prompting.swap(0, Ordering::SeqCst);
+                                        let _ = handle_nonstop_fail(&recipe);
+                                        break 'again None;
+                                    } // skip
+                                    4 => {
+                                        fetcher_stopped.store(true, Ordering::SeqCst);
+                                        fetcher_prompting.swap(0, Ordering::SeqCst);
+                                        break 'done;
+                                    } // done
+                                    _ => unreachable!(),
+                                }
                             }
                         }
                     }
+                };
+                fetcher_queue.lock().unwrap().fetching.remove(&source_key);
+                if fetcher_tx.send((index, fetched)).is_err() {
+                    // Cooker thread died
+                    break 'done;
+                }
+                if fetcher_config.cook.nonstop && fetcher_prompting.load(Ordering::SeqCst) == 4 {
+                    break 'done;
+                }
+            }
+            // stop other fetchers too
+            fetcher_queue.lock().unwrap().pending.clear();
+        }));
+    }
+    drop(fetched_tx);
+
+    // ---- Orderer Thread ----
+    let orderer_handle = thread::spawn(move || {
+        let mut next_index = 0;
+        let mut fetched = BTreeMap::new();
+        'done: for (index, result) in fetched_rx {
+            fetched.insert(index, result);
+            while let Some(result) = fetched.remove(&next_index) {
+                next_index += 1;
+                // skipped recipes are not cooked
+                if let Some(work) = result
+                    && work_tx.send(work).is_err()
+                {
+                    // Cooker thread died
+                    break 'done;
                 }
             }
         }
@@ -743,7 +867,10 @@ pub fn run_tui_cook(config: CliConfig, recipes: Vec<CookRecipe>) -> Result<TuiAp
         kill_everything(None);
     }
 
-    let _ = fetcher_handle.join();
+    for fetcher_handle in fetcher_handles {
+        let _ = fetcher_handle.join();
+    }
+    let _ = orderer_handle.join();
     let _ = cooker_handle.join();
 
     Ok(app)
@@ -1,19 +1,22 @@
 use cookbook::config::{CookConfig, CookLockOpt, get_config, init_config};
 use cookbook::cook::cook_build::{build, get_stage_dirs, remove_stage_dir};
+use cookbook::cook::cook_test;
 use cookbook::cook::fetch::{FetchResult, fetch, fetch_offline};
 use cookbook::cook::fs::{
-    create_dir, create_target_dir, get_git_commit_date, get_git_head_rev, get_git_rev_before_date,
-    remove_all, run_command,
+    create_dir, create_target_dir, get_file_blake3, get_git_commit_date, get_git_head_rev,
+    get_git_rev_before_date, get_git_tag_rev, read_to_string, remove_all, run_command,
 };
 use cookbook::cook::package::{package, package_handle_push};
 use cookbook::cook::pty::{PtyOut, flush_pty, write_to_pty};
 use cookbook::cook::tree::{self, DisplayOptions, TreeData, TreeItem, TreeOptions, WalkTreeEntry};
 use cookbook::cook::tui::join_logs;
-use cookbook::cook::{fetch_repo, ident};
+use cookbook::cook::vendor::VendorBundle;
+use cookbook::cook::{cache, fetch_repo, ident};
 use cookbook::recipe::{
-    CookRecipe, SourceRecipe, recipes_flatten_package_names, recipes_mark_as_deps,
+    BuildKind, CookRecipe, ExtraSource, Recipe, SourceRecipe, VariantSelection,
+    recipes_flatten_package_names, recipes_mark_as_deps, split_rule,
 };
-use cookbook::{Error, Result, staged_pkg};
+use cookbook::{Error, Result, format, lint, outdated, scaffold, staged_pkg};
 use pkg::{PackageName, PackageState};
 use redox_installer::PackageConfig;
 use std::collections::{BTreeMap, HashMap, HashSet};
@@ -22,6 +25,7 @@ use std::path::PathBuf;
 use std::process::Command;
 use std::str::FromStr;
 use std::sync::{OnceLock, mpsc};
+use std::time::Duration;
 use std::{env, fs};
 use std::{process, thread};
 use termion::{color, style};
@@ -44,14 +48,24 @@ const REPO_HELP_STR: &str = r#"
         repo-list    show list of recipes
         cook-list    show list of recipes to build
         push-list    show list of recipes to package
-        capture-rev  write lock to git recipes
+        capture-rev  write lock to git recipes, and blake3 to tar recipes
         change-rule  override rule to recipes
         change-rule-local  override rule to specific recipes
+        lint         check recipe.toml files for mistakes
+        license-report  show license metadata as CSV (always include package deps)
+        test         build recipe packages and run their [test] script
+        outdated     show recipes with newer upstream git tags or tar versions
+        fmt          format recipe.toml files in canonical order
+        new          create <category>/<name> recipe, requires --from
+        cache prune  delete cached source tarballs not used by any recipe
+                     and not used by any checkout in the last 30 days
+        vendor       copy sources of recipes into an offline bundle, requires -o
 
     common flags:
         --cookbook=<cookbook_dir>  the "recipes" folder, default to $PWD/recipes
         --repo=<repo_dir>          the "repo" folder, default to $PWD/repo
         --with-package-deps        include package deps (always implied in push command)
+        --with-tests               used in "cook", run [test] scripts and fail if any fails
         --all                      apply to all recipes in <cookbook_dir>
         --all-compiled             apply to all compiled recipes in <cookbook_dir>
         --all-binaries             apply to all compiled recipes in <cookbook_dir> that is configured as "binary"
@@ -64,6 +78,9 @@ const REPO_HELP_STR: &str = r#"
         --set-rule=<rule>          used in "change-rule", set wanted config rule
         --rollback                 used in "capture-rev", allow git to rollback
         --unset                    used in "capture-rev" and "change-rule", unset locks
+        --check                    used in "fmt", only report files that are not formatted
+        --from=<path-or-url>       used in "new", git or tar URL, or local source dir or tar
+        -o, --output=<dir>         used in "vendor", the bundle dir
 
     cook env and their defaults:
         CI=                          set to any value to disable TUI
@@ -79,6 +96,13 @@ const REPO_HELP_STR: &str = r#"
         COOKBOOK_CLEAN_TARGET=false  remove target directory after building
         COOKBOOK_WRITE_FILETREE=false whether to write stage files tree
         COOKBOOK_MAKE_JOBS=          override build jobs count from nproc
+        COOKBOOK_FETCH_JOBS=4        how many recipes to fetch in parallel on TUI
+        COOKBOOK_SOURCE_CACHE=build/cache/sources  shared cache of source tarballs
+                                        set to empty to disable the cache
+        COOKBOOK_GIT_CACHE=          dir for shared bare mirrors of git sources, disabled by default
+                                        sources cloned with it break if it's deleted
+        COOKBOOK_VENDOR=             bundle made by "repo vendor" to fetch sources from
+                                        use with COOKBOOK_OFFLINE=true on air-gapped hosts
         COOKBOOK_WEB=false           whether to generate package web files
 "#;
 
@@ -96,7 +120,14 @@ struct CliConfig {
     no_metadata: bool,
     with_rollback: bool,
     with_package_deps: bool,
+    with_tests: bool,
+    check: bool,
+    new_recipe: Option<String>,
+    from: Option<String>,
+    output: Option<PathBuf>,
     all: Option<AllOption>,
+    /// variants selected in the filesystem config or lock rules
+    variants: VariantSelection,
     cook: CookConfig,
 }
 
@@ -114,6 +145,14 @@ enum CliCommand {
     CaptureRev,
     ChangeRule,
     ChangeRuleLocal,
+    Lint,
+    LicenseReport,
+    Test,
+    Outdated,
+    Fmt,
+    New,
+    CachePrune,
+    Vendor,
 }
 
 #[derive(Clone)]
@@ -143,6 +182,7 @@ impl CliCommand {
             || *self == CliCommand::CookList
             || *self == CliCommand::CaptureRev
             || *self == CliCommand::ChangeRule
+            || *self == CliCommand::Vendor
     }
     pub fn is_pushing(&self) -> bool {
         *self == CliCommand::Push || *self == CliCommand::PushList
@@ -183,6 +223,15 @@ impl FromStr for CliCommand {
             "capture-rev" => Ok(CliCommand::CaptureRev),
             "change-rule" => Ok(CliCommand::ChangeRule),
             "change-rule-local" => Ok(CliCommand::ChangeRuleLocal),
+            "lint" => Ok(CliCommand::Lint),
+            "license-report" => Ok(CliCommand::LicenseReport),
+            "test" => Ok(CliCommand::Test),
+            "outdated" => Ok(CliCommand::Outdated),
+            "fmt" => Ok(CliCommand::Fmt),
+            "new" => Ok(CliCommand::New),
+            "cache prune" => Ok(CliCommand::CachePrune),
+            "cache" => bail_options_err!("Error: Usage is \"cache prune\""),
+            "vendor" => Ok(CliCommand::Vendor),
             _ => bail_options_err!("Unknown command {:?}", s),
         }
     }
@@ -203,6 +252,14 @@ impl ToString for CliCommand {
             CliCommand::CaptureRev => "capture-rev".to_string(),
             CliCommand::ChangeRule => "change-rule".to_string(),
             CliCommand::ChangeRuleLocal => "change-rule-local".to_string(),
+            CliCommand::Lint => "lint".to_string(),
+            CliCommand::LicenseReport => "license-report".to_string(),
+            CliCommand::Test => "test".to_string(),
+            CliCommand::Outdated => "outdated".to_string(),
+            CliCommand::Fmt => "fmt".to_string(),
+            CliCommand::New => "new".to_string(),
+            CliCommand::CachePrune => "cache prune".to_string(),
+            CliCommand::Vendor => "vendor".to_string(),
         }
     }
 }
@@ -224,8 +281,14 @@ impl CliConfig {
             display: DisplayOptions::Tree,
             sysroot_dir: current_dir.join("sysroot"),
             with_package_deps: false,
+            with_tests: false,
+            check: false,
+            new_recipe: None,
+            from: None,
+            output: None,
             cook: get_config().cook.clone(),
             all: None,
+            variants: VariantSelection::new(),
             unset: false,
             no_metadata: false,
             filesystem: None,
@@ -284,6 +347,9 @@ fn main_inner() -> Result<()> {
             }
             Err(e) => return Err(e),
         }
+        if config.with_tests {
+            handle_test(&recipes)?;
+        }
         return publish_packages(&recipes, &config.repo_dir);
     }
     if command.is_tree() {
@@ -295,6 +361,27 @@ fn main_inner() -> Result<()> {
     if command == CliCommand::Push {
         return handle_push(&recipes, &config);
     }
+    if command == CliCommand::Lint {
+        return handle_lint(&recipes);
+    }
+    if command == CliCommand::LicenseReport {
+        return handle_license_report(&recipes);
+    }
+    if command == CliCommand::Outdated {
+        return handle_outdated(&recipes);
+    }
+    if command == CliCommand::Fmt {
+        return handle_fmt(&recipes, &config);
+    }
+    if command == CliCommand::New {
+        return handle_new(&config);
+    }
+    if command == CliCommand::CachePrune {
+        return handle_cache_prune(&config);
+    }
+    if command == CliCommand::Vendor {
+        return handle_vendor(&recipes, &config);
+    }
 
     let verbose = config.cook.verbose;
     for recipe in &recipes {
@@ -326,6 +413,9 @@ fn main_inner() -> Result<()> {
     }
 
     if command == CliCommand::Cook {
+        if config.with_tests {
+            handle_test(&recipes)?;
+        }
         return publish_packages(&recipes, &config.repo_dir);
     }
 
@@ -468,7 +558,8 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
     let mut command: Option<String> = None;
     let mut recipe_names: Vec<PackageName> = Vec::new();
     let mut override_filesystem_repo_binary = false;
-    for arg in args {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
         if arg.starts_with("--") {
             if let Some((key, value)) = arg.split_once('=') {
                 match key {
@@ -477,6 +568,8 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
                     "--sysroot" => config.sysroot_dir = PathBuf::from(value),
                     "--category" => config.category = Some(PathBuf::from(value)),
                     "--set-rule" => config.set_rule = Some(value.into()),
+                    "--from" => config.from = Some(value.into()),
+                    "--output" => config.output = Some(PathBuf::from(value)),
                     "--display" => config.display = DisplayOptions::from_str(value)?,
                     "--filesystem" => {
                         config.filesystem = Some({
@@ -495,6 +588,8 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
                 match arg.as_str() {
                     "--repo-binary" => override_filesystem_repo_binary = true,
                     "--with-package-deps" => config.with_package_deps = true,
+                    "--with-tests" => config.with_tests = true,
+                    "--check" => config.check = true,
                     "--no-metadata" => config.no_metadata = true,
                     "--rollback" => config.with_rollback = true,
                     "--unset" => config.unset = true,
@@ -506,11 +601,23 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
             }
         } else if arg.starts_with('-') {
             match arg.as_str() {
+                "-o" => {
+                    let Some(value) = args.next() else {
+                        bail_options_err!("Error: Flag {} needs a value", arg);
+                    };
+                    config.output = Some(PathBuf::from(value));
+                }
                 _ => bail_options_err!("Error: Unknown flag: {}", arg),
             }
         } else if command.is_none() {
             // The first non-flag argument is the command
             command = Some(arg);
+        } else if command.as_deref() == Some("new") && config.new_recipe.is_none() {
+            // "new" takes a recipe path, not a recipe name
+            config.new_recipe = Some(arg);
+        } else if command.as_deref() == Some("cache") {
+            // "cache" takes a subcommand
+            command = Some(format!("cache {arg}"));
         } else {
             // Subsequent non-flag arguments are recipe names
             recipe_names.push(arg.try_into().map_err(Error::from)?);
@@ -542,7 +649,27 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
             }
             str::parse(&command)?
         };
-    if command.is_informational() {
+    if command == CliCommand::New {
+        if config.new_recipe.is_none() || config.from.is_none() {
+            bail_options_err!("Error: Usage is \"new <category>/<name> --from=<path-or-url>\"");
+        }
+        return Ok((config, command, Vec::new()));
+    }
+    if command == CliCommand::Vendor && config.output.is_none() {
+        bail_options_err!("Error: Usage is \"vendor -o <dir>\" with recipes or --filesystem");
+    }
+    if command == CliCommand::CachePrune {
+        // every recipe is checked, so the cache is never pruned partially
+        return Ok((config, command, Vec::new()));
+    }
+    let command = if command == CliCommand::Test {
+        // tests run after cooking, so this is "cook --with-tests"
+        config.with_tests = true;
+        CliCommand::Cook
+    } else {
+        command
+    };
+    if command.is_informational() || command == CliCommand::LicenseReport {
         // avoid extra data that clobber stdout
         config.cook.verbose = false;
     }
@@ -579,7 +706,8 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
 
             for path in all_recipes_path {
                 // TODO: Allow selecting recipes from category as host?
-                let recipe = match CookRecipe::from_path(&path, !command.is_cleaning(), false) {
+                let read_recipe = !command.is_cleaning() && command != CliCommand::Lint;
+                let recipe = match CookRecipe::from_path(&path, read_recipe, false) {
                     Ok(recipe) => recipe,
                     Err(_) if matches!(config.all, Some(AllOption::All)) => continue,
                     Err(e) => return Err(e.into()),
@@ -603,9 +731,26 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
         }
     }
 
-    if command.is_cleaning() {
+    if command == CliCommand::Lint {
+        let recipes = if preloaded_recipes.is_empty() {
+            let mut recipes = Vec::new();
+            for recipe_name in recipe_names {
+                let dir = staged_pkg::find(recipe_name.name())
+                    .ok_or_else(|| pkg::PackageError::PackageNotFound(recipe_name.clone()))?;
+                // recipe.toml is read later, so parse errors are reported as lint issues
+                recipes.push(CookRecipe::from_path(dir, false, false)?);
+            }
+            recipes
+        } else {
+            preloaded_recipes.into_values().collect()
+        };
+
+        return Ok((config, command, recipes));
+    }
+
+    if command.is_cleaning() || command == CliCommand::Outdated || command == CliCommand::Fmt {
         let recipes = if preloaded_recipes.is_empty() {
-            CookRecipe::from_list(recipe_names)?
+            CookRecipe::from_list(recipe_names, &config.variants)?
         } else {
             preloaded_recipes.into_values().collect()
         };
@@ -648,6 +793,7 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
                 } else {
                     default_rule
                 };
+                let (rule, _) = split_rule(rule);
 
                 if rule == "source" || rule == "local" {
                     source_names.push(recipe_name);
@@ -656,8 +802,17 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
                 }
             }
         }
-        source_names = CookRecipe::get_all_deps_names_recursive(&source_names, true)?;
-        binary_names = CookRecipe::get_all_deps_names_recursive(&binary_names, false)?;
+        // variants must be known before loading recipes, as they may add dependencies
+        config.variants = special_rules
+            .iter()
+            .map(|(name, rule)| {
+                let variants = split_rule(rule).1.into_iter().map(String::from);
+                (name.name().to_string(), variants.collect())
+            })
+            .collect();
+        let variants = &config.variants;
+        source_names = CookRecipe::get_all_deps_names_recursive(&source_names, true, variants)?;
+        binary_names = CookRecipe::get_all_deps_names_recursive(&binary_names, false, variants)?;
         let source_names: HashSet<PackageName> = source_names.into_iter().collect();
         let binary_names: HashSet<PackageName> = binary_names.into_iter().collect();
 
@@ -673,7 +828,7 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
             } else {
                 if special_rules
                     .get(recipe_name)
-                    .is_some_and(|s| s == "ignore")
+                    .is_some_and(|s| split_rule(s).0 == "ignore")
                 {
                     ignore_recipe_names.push(recipe_name.clone());
                 } else if repo_binary {
@@ -684,11 +839,12 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
             }
         }
 
-        if config.with_package_deps || command.is_pushing() {
+        if config.with_package_deps || command.is_pushing() || command == CliCommand::LicenseReport
+        {
             source_recipe_names =
-                CookRecipe::get_package_deps_recursive(&source_recipe_names, true)?;
+                CookRecipe::get_package_deps_recursive(&source_recipe_names, true, variants)?;
             binary_recipe_names =
-                CookRecipe::get_package_deps_recursive(&binary_recipe_names, true)?;
+                CookRecipe::get_package_deps_recursive(&binary_recipe_names, true, variants)?;
         }
 
         let mut recipes = if matches!(config.all, Some(AllOption::AllBinaries)) {
@@ -705,7 +861,7 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
                 while i < source_recipe_names.len() {
                     let name = &source_recipe_names[i];
                     match special_rules.get(name) {
-                        Some(s) if s.as_str() == "source" && binary_names.contains(name) => {
+                        Some(s) if split_rule(s).0 == "source" && binary_names.contains(name) => {
                             let bin = source_recipe_names.remove(i);
                             binary_recipe_names.push(bin);
                             continue;
@@ -715,18 +871,18 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
                     i += 1;
                 }
             }
-            CookRecipe::get_build_deps_recursive(&source_recipe_names, include_dev)?
+            CookRecipe::get_build_deps_recursive(&source_recipe_names, include_dev, variants)?
         } else {
-            CookRecipe::from_list(source_recipe_names.clone())?
+            CookRecipe::from_list(source_recipe_names.clone(), variants)?
         };
 
         let binary_recipes = if command.is_building() || command.is_pushing() {
-            CookRecipe::get_build_deps_recursive(&binary_recipe_names, false)?
+            CookRecipe::get_build_deps_recursive(&binary_recipe_names, false, variants)?
         } else {
-            CookRecipe::from_list(binary_recipe_names.clone())?
+            CookRecipe::from_list(binary_recipe_names.clone(), variants)?
         };
 
-        let ignore_recipes = CookRecipe::from_list(ignore_recipe_names.clone())?;
+        let ignore_recipes = CookRecipe::from_list(ignore_recipe_names.clone(), variants)?;
 
         recipes.extend(binary_recipes);
         recipes.extend(ignore_recipes);
@@ -736,7 +892,7 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
             if let Some(special_rule) =
                 special_rules.get(recipe.canon_recipe_name().without_prefix())
             {
-                if recipe.name.is_host() && special_rule == "binary" {
+                if recipe.name.is_host() && split_rule(special_rule).0 == "binary" {
                     // host recipe binaries is currently not supported
                     continue;
                 }
@@ -808,6 +964,10 @@ fn parse_args(args: Vec<String>) -> Result<(CliConfig, CliCommand, Vec<CookRecip
         recipes_mark_as_deps(&recipe_names, &mut recipes);
     }
 
+    if command.is_building() {
+        CookRecipe::check_constraints(&recipes, &config.variants)?;
+    }
+
     Ok((config, command, recipes))
 }
 
@@ -837,6 +997,7 @@ fn handle_cook(
         &target_dir,
         recipe,
         &config.cook,
+        &config.variants,
         logger,
     )?;
 
@@ -910,10 +1071,295 @@ fn handle_clean(recipe: &CookRecipe, config: &CliConfig, command: &CliCommand) -
                 cached = false;
             }
         }
+        let extra = match &recipe.recipe.source {
+            Some(SourceRecipe::Git { extra, .. }) | Some(SourceRecipe::Tar { extra, .. }) => {
+                extra.as_slice()
+            }
+            _ => &[],
+        };
+        for e in extra {
+            let ExtraSource::Tar { blake3, .. } = &e.source else {
+                continue;
+            };
+            let tar = recipe
+                .dir
+                .join(format!("source.{}.tar", e.dir.replace('/', "_")));
+            if tar.is_file() && (blake3.is_none() || config.all.is_some()) {
+                remove_all(&tar)?;
+                cached = false;
+            }
+        }
     }
     Ok(cached)
 }
 
+fn handle_test(recipes: &Vec<CookRecipe>) -> Result<()> {
+    let mut tested_count = 0;
+    let mut failed_count = 0;
+    for recipe in recipes.iter().filter(|r| !r.is_deps) {
+        match cook_test::test(recipe, &None) {
+            Ok(false) => continue,
+            Ok(true) => print_success(&CliCommand::Test, &recipe.name),
+            Err(e) => {
+                eprintln!("{}", e);
+                print_failed(&CliCommand::Test, &recipe.name);
+                failed_count += 1;
+            }
+        }
+        tested_count += 1;
+    }
+
+    check_failed_count(failed_count, tested_count, |n| {
+        format!("Tests failed in {n} tested")
+    })
+}
+
+fn handle_new(config: &CliConfig) -> Result<()> {
+    let new_recipe = config.new_recipe.as_ref().unwrap();
+    let Some((category, name)) = new_recipe.rsplit_once('/') else {
+        bail_options_err!("Error: Recipe path {:?} has no category", new_recipe);
+    };
+    let name = PackageName::new(name).map_err(Error::from)?;
+    if let Some(dir) = staged_pkg::find(name.name()) {
+        return Err(Error::Other(format!(
+            "Recipe {} already exists in {}",
+            name.as_str(),
+            dir.display()
+        )));
+    }
+    let recipe_dir = PathBuf::from("recipes").join(category).join(name.name());
+    let recipe_file = scaffold::new_recipe(&recipe_dir, config.from.as_ref().unwrap(), &None)?;
+    // make sure it parses, as the source may be unusual
+    Recipe::new(&recipe_file)?;
+    println!(
+        "Created {}, please review it before building:\n",
+        recipe_file.display()
+    );
+    print!("{}", read_to_string(&recipe_file)?);
+    Ok(())
+}
+
+fn handle_cache_prune(config: &CliConfig) -> Result<()> {
+    if config.cook.source_cache.as_os_str().is_empty() {
+        bail_options_err!("Error: The source cache is disabled");
+    }
+    let mut keep = HashSet::new();
+    for dir in staged_pkg::list() {
+        // the tarball of a broken recipe is still kept while it's recently used
+        let recipe = match Recipe::new(&dir.join("recipe.toml")) {
+            Ok(recipe) => recipe,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        let (blake3, extra) = match &recipe.source {
+            Some(SourceRecipe::Tar { blake3, extra, .. }) => (blake3.clone(), extra),
+            Some(SourceRecipe::Git { extra, .. }) => (None, extra),
+            _ => continue,
+        };
+        keep.extend(blake3);
+        for e in extra {
+            if let ExtraSource::Tar {
+                blake3: Some(blake3),
+                ..
+            } = &e.source
+            {
+                keep.insert(blake3.clone());
+            }
+        }
+    }
+
+    // the cache may be shared with other checkouts, which may use other tarballs
+    let max_age = Duration::from_secs(30 * 24 * 60 * 60);
+    let (count, size) = cache::cache_prune(&config.cook.source_cache, &keep, max_age)?;
+    println!(
+        "Removed {} cached {} from {}, freed {:.1} MiB",
+        count,
+        if count == 1 { "file" } else { "files" },
+        config.cook.source_cache.display(),
+        size as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+fn handle_vendor(recipes: &Vec<CookRecipe>, config: &CliConfig) -> Result<()> {
+    let mut bundle = VendorBundle::open(config.output.as_ref().unwrap())?;
+    let mut result = Ok(());
+    let mut vendored_count = 0;
+    for recipe in recipes {
+        // always online, the bundle is what makes fetching offline possible
+        match handle_fetch(recipe, config, false, &None)
+            .and_then(|_| bundle.add_recipe(recipe, &None))
+        {
+            Ok(()) => {
+                print_success(&CliCommand::Vendor, &recipe.name);
+                vendored_count += 1;
+            }
+            Err(e) => {
+                if config.cook.nonstop && config.cook.verbose {
+                    eprintln!("{}", e);
+                }
+                print_failed(&CliCommand::Vendor, &recipe.name);
+                if !config.cook.nonstop {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    // keep what has been vendored, so running again continues from there
+    bundle.add_remotes()?;
+    bundle.save()?;
+    result?;
+    println!(
+        "Vendored {} of {} recipes into {}, fetch from it with:\n\
+        COOKBOOK_OFFLINE=true COOKBOOK_VENDOR={}",
+        vendored_count,
+        recipes.len(),
+        bundle.dir().display(),
+        bundle.dir().display()
+    );
+    Ok(())
+}
+
+fn handle_fmt(recipes: &Vec<CookRecipe>, config: &CliConfig) -> Result<()> {
+    let mut unformatted_count = 0;
+    for recipe in recipes {
+        let file = recipe.dir.join("recipe.toml");
+        let formatted = match format::format_recipe_file(&file) {
+            Ok(Some(formatted)) => formatted,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("{}", e);
+                print_failed(&CliCommand::Fmt, &recipe.name);
+                unformatted_count += 1;
+                continue;
+            }
+        };
+        if config.check {
+            eprintln!("{}: not formatted", file.display());
+            unformatted_count += 1;
+        } else {
+            fs::write(&file, formatted).map_err(|e| Error::from_io_error(e, "Writing recipe"))?;
+            print_success(&CliCommand::Fmt, &recipe.name);
+        }
+    }
+
+    check_failed_count(unformatted_count, recipes.len(), |n| {
+        format!("Found {n} not formatted")
+    })
+}
+
+fn handle_outdated(recipes: &Vec<CookRecipe>) -> Result<()> {
+    let mut rows = vec![(
+        "name".to_string(),
+        "current".to_string(),
+        "latest".to_string(),
+    )];
+    let lock = &get_config().recipe_lock;
+    for recipe in recipes {
+        let mut recipe = recipe.clone();
+        // the locked rev is what actually gets built
+        if let Some(gitrev) = lock
+            .get(recipe.name.as_str())
+            .and_then(|r| r.gitrev.clone())
+            && let Some(SourceRecipe::Git { rev, .. }) = &mut recipe.recipe.source
+        {
+            *rev = Some(gitrev);
+        }
+        match outdated::check_recipe(&recipe) {
+            Ok(Some(outdated)) => {
+                if let Some(latest) = outdated.latest() {
+                    rows.push((
+                        recipe.name.to_string(),
+                        outdated.current.clone(),
+                        latest.to_string(),
+                    ));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Skipping {}: {e}", recipe.name.as_str()),
+        }
+    }
+    let name_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+    let current_width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0);
+    for (name, current, latest) in &rows {
+        println!("{name:name_width$}  {current:current_width$}  {latest}");
+    }
+    Ok(())
+}
+
+fn handle_license_report(recipes: &Vec<CookRecipe>) -> Result<()> {
+    let csv_field = |s: &str| {
+        if s.contains([',', '"', '\n']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut missing_count = 0;
+    println!("name,version,license,homepage,upstream_url,maintainers");
+    for recipe in recipes {
+        let meta = &recipe.recipe.package;
+        if meta.license.is_none() && recipe.recipe.build.kind != BuildKind::None {
+            missing_count += 1;
+        }
+        println!(
+            "{},{},{},{},{},{}",
+            recipe.name.as_str(),
+            csv_field(&recipe.guess_version().unwrap_or_default()),
+            csv_field(meta.license.as_deref().unwrap_or("")),
+            csv_field(meta.homepage.as_deref().unwrap_or("")),
+            csv_field(meta.upstream_url.as_deref().unwrap_or("")),
+            csv_field(&meta.maintainers.join(";")),
+        );
+    }
+    if missing_count > 0 {
+        eprintln!(
+            "WARNING: {} of {} recipes have no license",
+            missing_count,
+            recipes.len()
+        );
+    }
+    Ok(())
+}
+
+fn handle_lint(recipes: &Vec<CookRecipe>) -> Result<()> {
+    let mut failed_count = 0;
+    for recipe in recipes {
+        let issues = lint::lint_recipe(&recipe.dir);
+        if issues.is_empty() {
+            continue;
+        }
+        for issue in &issues {
+            eprintln!("{}: {}", recipe.dir.join("recipe.toml").display(), issue);
+        }
+        print_failed(&CliCommand::Lint, &recipe.name);
+        failed_count += 1;
+    }
+
+    check_failed_count(failed_count, recipes.len(), |n| {
+        format!("Found issues in {n}")
+    })
+}
+
+/// Fail if any recipe failed a check, `message` gets "N of M recipe(s)" to describe them
+fn check_failed_count(
+    failed_count: usize,
+    total: usize,
+    message: impl FnOnce(String) -> String,
+) -> Result<()> {
+    if failed_count == 0 {
+        return Ok(());
+    }
+    let recipes = if total == 1 { "recipe" } else { "recipes" };
+    Err(Error::Other(message(format!(
+        "{failed_count} of {total} {recipes}"
+    ))))
+}
+
 static PUSH_CONFIG: OnceLock<CliConfig> = OnceLock::new();
 fn handle_push(recipes: &Vec<CookRecipe>, config: &CliConfig) -> Result<()> {
     if !config.sysroot_dir.is_dir() {
@@ -922,6 +1368,51 @@ fn handle_push(recipes: &Vec<CookRecipe>, config: &CliConfig) -> Result<()> {
             config.sysroot_dir.display()
         )));
     }
+    // refuse to install conflicting packages into the same sysroot
+    for recipe in recipes.iter().filter(|r| !r.name.is_host()) {
+        for conflict in &recipe.recipe.package.conflicts {
+            if let Some(other) = recipes
+                .iter()
+                .find(|r| r.name.name() == conflict.name() && r.name != recipe.name)
+            {
+                return Err(Error::Other(format!(
+                    "Package {} conflicts with {}, they can't be pushed together",
+                    recipe.name.as_str(),
+                    other.name.as_str()
+                )));
+            }
+        }
+    }
+    // built packages must still satisfy the version constraints of their dependents
+    for recipe in recipes.iter().filter(|r| !r.name.is_host()) {
+        let constraints = &recipe.recipe.constraints;
+        let optional = recipe.recipe.optional_packages.iter().filter_map(|p| {
+            constraints
+                .optional
+                .get(&p.name)
+                .map(|c| (&p.dependencies, c))
+        });
+        let lists = std::iter::once((&recipe.recipe.package.dependencies, &constraints.package))
+            .chain(optional);
+        for (deps, constraints) in lists {
+            for dep in deps {
+                let Some(constraint) = constraints.get(dep.name()) else {
+                    continue;
+                };
+                let Ok(package) = staged_pkg::new(dep) else {
+                    continue;
+                };
+                if !constraint.matches(&package.version) {
+                    return Err(Error::Constraint {
+                        name: dep.clone(),
+                        version: package.version,
+                        constraint: constraint.clone(),
+                        chain: vec![recipe.name.clone()],
+                    });
+                }
+            }
+        }
+    }
     let recipe_map: HashMap<&PackageName, &CookRecipe> =
         recipes.iter().map(|r| (&r.name, r)).collect();
     PUSH_CONFIG
@@ -1065,6 +1556,24 @@ fn handle_change_rule(
             // host packages will always be "source" so it's pointless to change their rule
             continue;
         }
+        if is_capture_rev
+            && !config.unset
+            && let Some(SourceRecipe::Tar {
+                blake3: None,
+                sha256,
+                sha512,
+                ..
+            }) = &recipe.recipe.source
+        {
+            // without sha256 or sha512 there's nothing to verify the tar with
+            if sha256.is_some() || sha512.is_some() {
+                match capture_tar_blake3(recipe) {
+                    Ok(()) => print_success(command, &recipe.name),
+                    Err(e) => eprintln!("Skipping {}: {e}", recipe.name.as_str()),
+                }
+            }
+            continue;
+        }
         if is_capture_rev && !matches!(recipe.recipe.source, Some(SourceRecipe::Git { .. })) {
             continue;
         }
@@ -1089,15 +1598,19 @@ fn handle_change_rule(
                 recipe_lock.gitrev.take().is_none()
             } else {
                 let source_dir = recipe.dir.join("source");
-                let rev = if config.with_rollback {
-                    // invoke fetch as the git tracking can be different
-                    match handle_fetch(recipe, config, false, &None) {
-                        Ok(_) => get_git_rev_before_date(&source_dir, &cookbook_date),
-                        Err(e) => Err(e),
-                    }
-                } else {
-                    get_git_head_rev(&source_dir).map(|r| r.0)
-                };
+                let rev =
+                    if let Some(SourceRecipe::Git { tag: Some(tag), .. }) = &recipe.recipe.source {
+                        // tag is already a fixed point, record the commit it resolves to
+                        get_git_tag_rev(&source_dir, tag, &None)
+                    } else if config.with_rollback {
+                        // invoke fetch as the git tracking can be different
+                        match handle_fetch(recipe, config, false, &None) {
+                            Ok(_) => get_git_rev_before_date(&source_dir, &cookbook_date),
+                            Err(e) => Err(e),
+                        }
+                    } else {
+                        get_git_head_rev(&source_dir).map(|r| r.0)
+                    };
                 match rev {
                     Ok(rev) => {
                         let old_rev = recipe_lock.gitrev.replace(rev.clone());
@@ -1138,6 +1651,31 @@ fn handle_change_rule(
     Ok(())
 }
 
+/// Write blake3 of the tar source into recipe.toml, once verified by upstream sha256 or sha512
+fn capture_tar_blake3(recipe: &CookRecipe) -> Result<()> {
+    // fetch verifies the downloaded tar against the upstream checksum
+    fetch(recipe, true, &None)?;
+    let blake3 = get_file_blake3(&recipe.dir.join("source.tar"))?;
+
+    let file = recipe.dir.join("recipe.toml");
+    let mut doc = read_to_string(&file)?
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| Error::Other(format!("Parsing {}: {e}", file.display())))?;
+    let Some(source) = doc
+        .get_mut("source")
+        .and_then(|s| s.as_table_like_mut())
+        .filter(|s| s.contains_key("tar"))
+    else {
+        // the tar source is inherited with "extends"
+        return Err(Error::Other(format!(
+            "tar source is not declared in {}",
+            file.display()
+        )));
+    };
+    source.insert("blake3", toml_edit::value(blake3));
+    fs::write(&file, doc.to_string()).map_err(|e| Error::from_io_error(e, "Writing recipe"))
+}
+
 macro_rules! bail_options_err {
     ($($arg:tt)*) => {
         return Err(cookbook::Error::Options(format!($($arg)*)))
@@ -150,7 +150,7 @@ impl App {
                 s.packages
                     .keys()
                     .filter_map(|p| PackageName::new(p).ok())
-                    .filter_map(|p| CookRecipe::from_name(p).ok())
+                    .filter_map(|p| CookRecipe::from_name(p, &Default::default()).ok())
                     .collect()
             })
             .unwrap_or(vec![]);
@@ -1,6 +1,7 @@
 use std::{
     collections::{BTreeMap, HashMap},
     fs,
+    path::PathBuf,
     str::FromStr,
     sync::OnceLock,
 };
@@ -14,6 +15,8 @@ pub struct CookConfigOpt {
     pub offline: Option<bool>,
     /// whether to set jobs number instead of from nproc
     pub jobs: Option<usize>,
+    /// how many recipes to fetch in parallel on TUI
+    pub fetch_jobs: Option<usize>,
     /// whether to use TUI to allow parallel build
     /// default value is yes if "CI" env unset and STDIN is open.
     pub tui: Option<bool>,
@@ -39,12 +42,22 @@ pub struct CookConfigOpt {
     pub clean_target: Option<bool>,
     /// whether to always write stage.files metadata
     pub write_filetree: Option<bool>,
+    /// where to cache downloaded source tarballs, can be shared between checkouts,
+    /// set to empty to disable the cache
+    pub source_cache: Option<PathBuf>,
+    /// where to keep bare mirrors of git sources, which clones borrow objects from,
+    /// so it must not be deleted while those clones exist, empty by default
+    pub git_cache: Option<PathBuf>,
+    /// where an offline bundle made by "repo vendor" is, which sources are fetched from,
+    /// empty by default
+    pub vendor: Option<PathBuf>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct CookConfig {
     pub offline: bool,
     pub jobs: usize,
+    pub fetch_jobs: usize,
     pub tui: bool,
     pub logs: bool,
     pub nonstop: bool,
@@ -55,6 +68,9 @@ pub struct CookConfig {
     pub clean_build: bool,
     pub clean_target: bool,
     pub write_filetree: bool,
+    pub source_cache: PathBuf,
+    pub git_cache: PathBuf,
+    pub vendor: PathBuf,
 }
 
 impl From<CookConfigOpt> for CookConfig {
@@ -62,6 +78,7 @@ impl From<CookConfigOpt> for CookConfig {
         CookConfig {
             offline: value.offline.unwrap(),
             jobs: value.jobs.unwrap(),
+            fetch_jobs: value.fetch_jobs.unwrap(),
             tui: value.tui.unwrap(),
             logs: value.logs.unwrap(),
             nonstop: value.nonstop.unwrap(),
@@ -72,6 +89,9 @@ impl From<CookConfigOpt> for CookConfig {
             clean_build: value.clean_build.unwrap(),
             clean_target: value.clean_target.unwrap(),
             write_filetree: value.write_filetree.unwrap(),
+            source_cache: value.source_cache.unwrap(),
+            git_cache: value.git_cache.unwrap(),
+            vendor: value.vendor.unwrap(),
         }
     }
 }
@@ -156,6 +176,9 @@ pub fn init_config() {
                 .unwrap_or(1),
         ));
     }
+    if config.cook_opt.fetch_jobs.is_none() {
+        config.cook_opt.fetch_jobs = Some(extract_env("COOKBOOK_FETCH_JOBS", 4));
+    }
     if config.cook_opt.logs.is_none() {
         let default = config.cook_opt.tui.unwrap();
         config.cook_opt.logs = Some(extract_env("COOKBOOK_LOGS", default));
@@ -191,6 +214,18 @@ pub fn init_config() {
             config.cook_opt.clean_target.unwrap_or(false) || extract_env("COOKBOOK_WEB", false),
         ));
     }
+    if config.cook_opt.source_cache.is_none() {
+        config.cook_opt.source_cache = Some(extract_env(
+            "COOKBOOK_SOURCE_CACHE",
+            PathBuf::from("build/cache/sources"),
+        ));
+    }
+    if config.cook_opt.git_cache.is_none() {
+        config.cook_opt.git_cache = Some(extract_env("COOKBOOK_GIT_CACHE", PathBuf::new()));
+    }
+    if config.cook_opt.vendor.is_none() {
+        config.cook_opt.vendor = Some(extract_env("COOKBOOK_VENDOR", PathBuf::new()));
+    }
     if config.mirrors.is_empty() {
         // The GNU FTP mirror below is automatically inserted for convenience
         // You can choose other mirrors by setting it on cookbook.toml
@@ -308,6 +343,7 @@ mod tests {
                 jobs: std::thread::available_parallelism()
                     .map(|f| usize::from(f))
                     .unwrap_or(1),
+                fetch_jobs: 4,
                 tui: true,
                 logs: true,
                 nonstop: false,
@@ -317,7 +353,10 @@ mod tests {
                 verbose_cmd: true,
                 clean_build: false,
                 clean_target: false,
-                write_filetree: false
+                write_filetree: false,
+                source_cache: PathBuf::from("build/cache/sources"),
+                git_cache: PathBuf::new(),
+                vendor: PathBuf::new(),
             }
         );
     }
@@ -1,11 +1,17 @@
+pub mod archive;
+pub mod cache;
 // avoid confusion with build.rs
 pub mod cook_build;
+pub mod cook_test;
+pub mod download;
 pub mod fetch;
 pub mod fetch_repo;
 pub mod fs;
+pub mod git_cache;
 pub mod ident;
 pub mod package;
 pub mod pty;
 pub mod script;
 pub mod tree;
 pub mod tui;
+pub mod vendor;
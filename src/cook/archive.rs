@@ -0,0 +1,542 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use flate2::CrcWriter;
+use flate2::read::DeflateDecoder;
+
+use crate::cook::fs::{create_dir, create_dir_clean, remove_all, rename, run_command, symlink};
+use crate::cook::pty::PtyOut;
+use crate::{Result, bail_other_err, log_to_pty, wrap_io_err};
+
+// This file contains code to extract non-tar source archives.
+// All extraction here strips the first path component, same as `tar --strip-components 1`.
+
+#[derive(Debug, PartialEq)]
+pub enum ArchiveFormat {
+    /// Anything that tar can autodetect (tar, tar.gz, tar.xz, ...)
+    Tar,
+    Zip,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    /// Detect archive format from magic bytes
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).map_err(wrap_io_err!(path, "Opening archive"))?;
+        let mut magic = [0u8; 6];
+        let mut len = 0;
+        while len < magic.len() {
+            match file.read(&mut magic[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(wrap_io_err!(path, "Reading archive")(e)),
+            }
+        }
+        Ok(Self::from_magic(&magic[..len]))
+    }
+
+    fn from_magic(magic: &[u8]) -> Self {
+        if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            ArchiveFormat::Zip
+        } else if magic.starts_with(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+            ArchiveFormat::SevenZip
+        } else {
+            ArchiveFormat::Tar
+        }
+    }
+}
+
+const ZIP_LOCAL_HEADER_SIG: u32 = 0x04034b50;
+const ZIP_CENTRAL_HEADER_SIG: u32 = 0x02014b50;
+const ZIP_EOCD_SIG: u32 = 0x06054b50;
+const ZIP64_EOCD_SIG: u32 = 0x06064b50;
+const ZIP64_EOCD_LOCATOR_SIG: u32 = 0x07064b50;
+const ZIP_CENTRAL_HEADER_LEN: u64 = 46;
+const ZIP_LOCAL_HEADER_LEN: u64 = 30;
+/// Longest symlink target accepted, same as PATH_MAX
+const ZIP_SYMLINK_MAX: u64 = 4096;
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    flags: u16,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    local_header_offset: u64,
+    /// unix mode, if the archive is created from unix
+    mode: Option<u32>,
+}
+
+fn le_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn le_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn le_u64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+fn read_exact_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn invalid_zip(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid zip archive: {msg}"),
+    )
+}
+
+/// Read list of entries from zip central directory
+fn read_zip_entries<R: Read + Seek>(reader: &mut R) -> io::Result<Vec<ZipEntry>> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    // EOCD is 22 bytes, followed by an optional comment up to 65535 bytes
+    let tail_len = file_len.min(22 + 65535);
+    let tail = read_exact_at(reader, file_len - tail_len, tail_len as usize)?;
+    let Some(eocd_at) = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| le_u32(&tail, i) == ZIP_EOCD_SIG)
+    else {
+        return Err(invalid_zip("end of central directory not found"));
+    };
+    let eocd = &tail[eocd_at..];
+    let mut entries_count = le_u16(eocd, 10) as u64;
+    let mut cd_size = le_u32(eocd, 12) as u64;
+    let mut cd_offset = le_u32(eocd, 16) as u64;
+
+    let eocd_offset = file_len - tail_len + eocd_at as u64;
+    if eocd_offset >= 20 {
+        let locator = read_exact_at(reader, eocd_offset - 20, 20)?;
+        if le_u32(&locator, 0) == ZIP64_EOCD_LOCATOR_SIG {
+            let eocd64_offset = le_u64(&locator, 8);
+            if eocd64_offset
+                .checked_add(56)
+                .is_none_or(|end| end > eocd_offset)
+            {
+                return Err(invalid_zip("zip64 end of central directory out of bounds"));
+            }
+            let eocd64 = read_exact_at(reader, eocd64_offset, 56)?;
+            if le_u32(&eocd64, 0) != ZIP64_EOCD_SIG {
+                return Err(invalid_zip("zip64 end of central directory not found"));
+            }
+            entries_count = le_u64(&eocd64, 32);
+            cd_size = le_u64(&eocd64, 40);
+            cd_offset = le_u64(&eocd64, 48);
+        }
+    }
+
+    // sizes come from the file, check them before allocating anything
+    if cd_offset
+        .checked_add(cd_size)
+        .is_none_or(|end| end > eocd_offset)
+    {
+        return Err(invalid_zip("central directory out of bounds"));
+    }
+    if entries_count > cd_size / ZIP_CENTRAL_HEADER_LEN {
+        return Err(invalid_zip("too many entries for central directory size"));
+    }
+    let cd = read_exact_at(reader, cd_offset, cd_size as usize)?;
+    let mut entries = Vec::new();
+    let mut at = 0;
+    for _ in 0..entries_count {
+        if at + 46 > cd.len() || le_u32(&cd, at) != ZIP_CENTRAL_HEADER_SIG {
+            return Err(invalid_zip("broken central directory"));
+        }
+        let made_by = le_u16(&cd, at + 4);
+        let name_len = le_u16(&cd, at + 28) as usize;
+        let extra_len = le_u16(&cd, at + 30) as usize;
+        let comment_len = le_u16(&cd, at + 32) as usize;
+        let external_attrs = le_u32(&cd, at + 38);
+        let name_end = at + 46 + name_len;
+        let extra_end = name_end + extra_len;
+        if extra_end + comment_len > cd.len() {
+            return Err(invalid_zip("broken central directory"));
+        }
+        let mut entry = ZipEntry {
+            name: String::from_utf8_lossy(&cd[at + 46..name_end]).to_string(),
+            flags: le_u16(&cd, at + 8),
+            method: le_u16(&cd, at + 10),
+            crc32: le_u32(&cd, at + 16),
+            compressed_size: le_u32(&cd, at + 20) as u64,
+            uncompressed_size: le_u32(&cd, at + 24) as u64,
+            local_header_offset: le_u32(&cd, at + 42) as u64,
+            // 3 = UNIX
+            mode: if made_by >> 8 == 3 {
+                Some(external_attrs >> 16)
+            } else {
+                None
+            },
+        };
+
+        // zip64 extended information, values only exist if the 32 bit field is saturated
+        let mut extra_at = name_end;
+        while extra_at + 4 <= extra_end {
+            let id = le_u16(&cd, extra_at);
+            let size = le_u16(&cd, extra_at + 2) as usize;
+            let data_end = (extra_at + 4 + size).min(extra_end);
+            if id == 0x0001 {
+                let mut field_at = extra_at + 4;
+                for value in [
+                    &mut entry.uncompressed_size,
+                    &mut entry.compressed_size,
+                    &mut entry.local_header_offset,
+                ] {
+                    if *value == 0xFFFFFFFF && field_at + 8 <= data_end {
+                        *value = le_u64(&cd, field_at);
+                        field_at += 8;
+                    }
+                }
+            }
+            extra_at = data_end;
+        }
+
+        // file data must be between the local header and the central directory
+        if entry
+            .local_header_offset
+            .checked_add(ZIP_LOCAL_HEADER_LEN)
+            .and_then(|end| end.checked_add(entry.compressed_size))
+            .is_none_or(|end| end > cd_offset)
+        {
+            return Err(invalid_zip(&format!("{:?} out of bounds", entry.name)));
+        }
+
+        entries.push(entry);
+        at = extra_end + comment_len;
+    }
+
+    Ok(entries)
+}
+
+/// Strip first component of an archive path, rejecting paths that escape the destination
+fn strip_first_component(name: &str) -> Option<PathBuf> {
+    let mut components = Path::new(name).components();
+    match components.next() {
+        Some(Component::Normal(_)) => {}
+        _ => return None,
+    }
+    let mut path = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(c) => path.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Check that a symlink at `link` (relative to the extraction root) pointing to `target`
+/// stays inside the extraction root. Links inside or targets passing through other symlinks
+/// are rejected too, as those can't be checked without resolving them.
+fn symlink_within_root(link: &Path, target: &str, symlinks: &HashSet<&Path>) -> bool {
+    if link.ancestors().skip(1).any(|p| symlinks.contains(p)) {
+        return false;
+    }
+    let mut path = link.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut components = Path::new(target).components().peekable();
+    while let Some(component) = components.next() {
+        if symlinks.contains(path.as_path()) {
+            return false;
+        }
+        match component {
+            Component::Normal(c) => path.push(c),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !path.pop() {
+                    return false;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+        // the last component may be a symlink, which is checked on its own
+        if components.peek().is_none() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Extract a zip file into `dest_dir`, stripping the first path component
+pub fn extract_zip(zip_path: &Path, dest_dir: &Path, logger: &PtyOut) -> Result<()> {
+    let verbose = crate::config::get_config().cook.verbose;
+    let mut file = File::open(zip_path).map_err(wrap_io_err!(zip_path, "Opening zip"))?;
+    let entries =
+        read_zip_entries(&mut file).map_err(wrap_io_err!(zip_path, "Reading zip entries"))?;
+
+    // symlinks are created last, so no file is written through them
+    let mut symlinks = Vec::new();
+    for entry in entries {
+        let Some(rel_path) = strip_first_component(&entry.name) else {
+            // tar --strip-components also silently skips these
+            continue;
+        };
+        let path = dest_dir.join(&rel_path);
+        if verbose {
+            log_to_pty!(logger, "{}", entry.name);
+        }
+
+        if entry.name.ends_with('/') {
+            create_dir(&path)?;
+            continue;
+        }
+        if entry.flags & 1 != 0 {
+            bail_other_err!("Encrypted zip entry {:?} is not supported", entry.name);
+        }
+        if let Some(parent) = path.parent() {
+            create_dir(parent)?;
+        }
+
+        let mode = entry.mode.unwrap_or(0);
+        if mode & 0o170000 == 0o120000 {
+            if entry.uncompressed_size > ZIP_SYMLINK_MAX {
+                bail_other_err!("Zip symlink {:?} has a too long target", entry.name);
+            }
+            let mut target = Vec::new();
+            extract_zip_entry(&mut file, &entry, &mut target)
+                .map_err(wrap_io_err!(zip_path, "Extracting zip entry"))?;
+            symlinks.push((rel_path, String::from_utf8_lossy(&target).to_string()));
+            continue;
+        }
+
+        let mut output = File::create(&path).map_err(wrap_io_err!(path, "Creating zip entry"))?;
+        extract_zip_entry(&mut file, &entry, &mut output)
+            .map_err(wrap_io_err!(zip_path, "Extracting zip entry"))?;
+        if mode & 0o777 != 0 {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))
+                .map_err(wrap_io_err!(path, "Setting permissions"))?;
+        }
+    }
+
+    let symlink_paths: HashSet<&Path> = symlinks.iter().map(|(p, _)| p.as_path()).collect();
+    for (rel_path, target) in &symlinks {
+        if !symlink_within_root(rel_path, target, &symlink_paths) {
+            bail_other_err!("Zip symlink {rel_path:?} to {target:?} points outside of the source");
+        }
+        symlink(target, dest_dir.join(rel_path))?;
+    }
+
+    Ok(())
+}
+
+/// Decompress an entry into `writer`, never writing more than its declared size
+fn extract_zip_entry<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    entry: &ZipEntry,
+    writer: W,
+) -> io::Result<()> {
+    let header = read_exact_at(
+        reader,
+        entry.local_header_offset,
+        ZIP_LOCAL_HEADER_LEN as usize,
+    )?;
+    if le_u32(&header, 0) != ZIP_LOCAL_HEADER_SIG {
+        return Err(invalid_zip("broken local header"));
+    }
+    let data_offset = entry.local_header_offset
+        + ZIP_LOCAL_HEADER_LEN
+        + le_u16(&header, 26) as u64
+        + le_u16(&header, 28) as u64;
+    reader.seek(SeekFrom::Start(data_offset))?;
+    let compressed = (&mut *reader).take(entry.compressed_size);
+
+    let data: Box<dyn Read + '_> = match entry.method {
+        0 => Box::new(compressed),
+        8 => Box::new(DeflateDecoder::new(compressed)),
+        method => {
+            return Err(invalid_zip(&format!(
+                "compression method {method} of {:?} is not supported",
+                entry.name
+            )));
+        }
+    };
+
+    // one byte more than declared is enough to know that the entry is broken
+    let mut data = data.take(entry.uncompressed_size.saturating_add(1));
+    let mut writer = CrcWriter::new(writer);
+    let len = io::copy(&mut data, &mut writer)?;
+    if len != entry.uncompressed_size || writer.crc().sum() != entry.crc32 {
+        return Err(invalid_zip(&format!(
+            "checksum mismatch on {:?}",
+            entry.name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Extract a 7z file into `dest_dir` using 7z program, stripping the first path component
+pub fn extract_7z(archive_path: &Path, dest_dir: &Path, logger: &PtyOut) -> Result<()> {
+    let extract_dir = dest_dir.with_added_extension("7z");
+    create_dir_clean(&extract_dir)?;
+
+    let mut command = Command::new("7z");
+    command.arg("x").arg("-y");
+    command.arg(format!("-o{}", extract_dir.display()));
+    command.arg(archive_path);
+    run_command(command, logger)?;
+
+    let top_entries =
+        fs::read_dir(&extract_dir).map_err(wrap_io_err!(extract_dir, "Reading extracted dir"))?;
+    for top_entry in top_entries {
+        let top_entry = top_entry.map_err(wrap_io_err!(extract_dir, "Reading file entry"))?;
+        let top_path = top_entry.path();
+        if !top_path.is_dir() {
+            continue;
+        }
+        let entries =
+            fs::read_dir(&top_path).map_err(wrap_io_err!(top_path, "Reading extracted dir"))?;
+        for entry in entries {
+            let entry = entry.map_err(wrap_io_err!(top_path, "Reading file entry"))?;
+            rename(&entry.path(), &dest_dir.join(entry.file_name()))?;
+        }
+    }
+
+    remove_all(&extract_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+
+    use crate::cook::archive::{
+        ArchiveFormat, extract_zip_entry, read_zip_entries, strip_first_component,
+        symlink_within_root,
+    };
+
+    /// Write a zip with deflated files, mimicking what zip(1) produces
+    fn make_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut cd = Vec::new();
+        for (name, content) in files {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(content).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let mut crc = flate2::Crc::new();
+            crc.update(content);
+
+            let offset = zip.len() as u32;
+            let mut common = Vec::new();
+            common.extend_from_slice(&20u16.to_le_bytes()); // version needed
+            common.extend_from_slice(&0u16.to_le_bytes()); // flags
+            common.extend_from_slice(&8u16.to_le_bytes()); // deflate
+            common.extend_from_slice(&0u32.to_le_bytes()); // time and date
+            common.extend_from_slice(&crc.sum().to_le_bytes());
+            common.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            common.extend_from_slice(&0u16.to_le_bytes()); // extra len
+
+            zip.extend_from_slice(&0x04034b50u32.to_le_bytes());
+            zip.extend_from_slice(&common);
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(&compressed);
+
+            cd.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            cd.extend_from_slice(&((3u16 << 8) | 20).to_le_bytes()); // made by unix
+            cd.extend_from_slice(&common);
+            cd.extend_from_slice(&0u16.to_le_bytes()); // comment len
+            cd.extend_from_slice(&0u16.to_le_bytes()); // disk number
+            cd.extend_from_slice(&0u16.to_le_bytes()); // internal attrs
+            cd.extend_from_slice(&((0o100755u32) << 16).to_le_bytes());
+            cd.extend_from_slice(&offset.to_le_bytes());
+            cd.extend_from_slice(name.as_bytes());
+        }
+        let cd_offset = zip.len() as u32;
+        zip.extend_from_slice(&cd);
+        zip.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]); // disk numbers
+        zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&cd_offset.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes()); // comment len
+        zip
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(
+            ArchiveFormat::from_magic(b"PK\x03\x04\x14\x00"),
+            ArchiveFormat::Zip
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]),
+            ArchiveFormat::SevenZip
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(&[0x1F, 0x8B, 0x08]),
+            ArchiveFormat::Tar
+        );
+    }
+
+    #[test]
+    fn zip_entries() {
+        let zip = make_zip(&[
+            ("foo-1.0/configure", b"#!/bin/sh\n"),
+            ("foo-1.0/src/main.c", b"int main() { return 0; }\n"),
+        ]);
+        let mut cursor = Cursor::new(zip);
+        let entries = read_zip_entries(&mut cursor).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "foo-1.0/src/main.c");
+        assert_eq!(entries[0].mode, Some(0o100755));
+
+        let mut data = Vec::new();
+        extract_zip_entry(&mut cursor, &entries[1], &mut data).unwrap();
+        assert_eq!(data, b"int main() { return 0; }\n");
+    }
+
+    #[test]
+    fn zip_sizes_out_of_bounds() {
+        let mut zip = make_zip(&[("foo-1.0/configure", b"#!/bin/sh\n")]);
+        // claim a central directory much larger than the file
+        let eocd = zip.len() - 22;
+        zip[eocd + 12..eocd + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read_zip_entries(&mut Cursor::new(zip)).is_err());
+    }
+
+    #[test]
+    fn zip_symlinks() {
+        let symlinks = HashSet::from([Path::new("lib/link")]);
+        let within =
+            |link: &str, target: &str| symlink_within_root(Path::new(link), target, &symlinks);
+        assert!(within("bin/tool", "../lib/tool"));
+        assert!(within("tool", "lib/link"));
+        assert!(!within("tool", "/etc/passwd"));
+        assert!(!within("bin/tool", "../../etc/passwd"));
+        assert!(!within("tool", "lib/link/../x"));
+        assert!(!within("lib/link/tool", "x"));
+        assert!(!within("tool", ""));
+    }
+
+    #[test]
+    fn strip_component() {
+        assert_eq!(
+            strip_first_component("foo-1.0/src/main.c"),
+            Some("src/main.c".into())
+        );
+        assert_eq!(strip_first_component("foo-1.0/"), None);
+        assert_eq!(strip_first_component("README"), None);
+        assert_eq!(strip_first_component("foo-1.0/../../etc/passwd"), None);
+        assert_eq!(strip_first_component("/foo-1.0/bar"), None);
+    }
+}
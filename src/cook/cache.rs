@@ -0,0 +1,183 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::Result;
+use crate::config::try_get_config;
+use crate::cook::fs::{create_dir, get_file_blake3, remove_all, rename};
+use crate::cook::vendor::vendor_dir;
+use crate::wrap_io_err;
+
+// This file contains the download cache of source tarballs, which are stored by their blake3
+// so it can be shared between recipes and cookbook checkouts, and survives "repo unfetch".
+// The modification time of cached tarballs is when they were last used.
+
+/// Temporary files younger than this may still be written by another process
+const TMP_MIN_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The configured cache dir, or None if the cache is disabled
+pub fn source_cache_dir() -> Option<PathBuf> {
+    let dir = &try_get_config()?.cook.source_cache;
+    if dir.as_os_str().is_empty() {
+        None
+    } else {
+        Some(dir.clone())
+    }
+}
+
+/// Copy a cached tarball into `dest`, returns false if it's not cached.
+/// Tarballs in the vendor bundle are used too, if there's one.
+pub fn cache_restore(blake3: &Option<String>, dest: &Path) -> Result<bool> {
+    let Some(blake3) = blake3 else {
+        return Ok(false);
+    };
+    if let Some(cache_dir) = source_cache_dir()
+        && restore(&cache_dir, blake3, dest)?
+    {
+        touch(&cache_dir.join(blake3));
+        return Ok(true);
+    }
+    match vendor_dir() {
+        Some(bundle_dir) => restore(&bundle_dir.join("sources"), blake3, dest),
+        None => Ok(false),
+    }
+}
+
+/// Store a tarball which has been verified to match `blake3`
+pub fn cache_store(blake3: &Option<String>, src: &Path) -> Result<()> {
+    match (source_cache_dir(), blake3) {
+        (Some(cache_dir), Some(blake3)) => store(&cache_dir, blake3, src),
+        _ => Ok(()),
+    }
+}
+
+fn restore(cache_dir: &Path, blake3: &str, dest: &Path) -> Result<bool> {
+    let cached = cache_dir.join(blake3);
+    if !cached.is_file() {
+        return Ok(false);
+    }
+    // the cache can be shared, so don't trust it blindly
+    if get_file_blake3(&cached)? != blake3 {
+        // it's still a miss if the cache is read only
+        let _ = remove_all(&cached);
+        return Ok(false);
+    }
+    link_or_copy(&cached, dest)?;
+    Ok(true)
+}
+
+pub(crate) fn store(cache_dir: &Path, blake3: &str, src: &Path) -> Result<()> {
+    let cached = cache_dir.join(blake3);
+    if cached.is_file() {
+        touch(&cached);
+        return Ok(());
+    }
+    create_dir(cache_dir)?;
+    // write to a temporary file first, as other checkouts may read it
+    let cached_tmp = cache_dir.join(format!("{blake3}.{}.tmp", std::process::id()));
+    link_or_copy(src, &cached_tmp)?;
+    rename(&cached_tmp, &cached)
+}
+
+/// Mark a cached tarball as used now. Failures are ignored, the cache may be read only.
+fn touch(path: &Path) {
+    let _ = fs::File::open(path).and_then(|f| f.set_modified(SystemTime::now()));
+}
+
+/// Hard link where possible, the cache may live on another filesystem
+fn link_or_copy(src: &Path, dst: &Path) -> Result<()> {
+    if fs::hard_link(src, dst).is_err() {
+        fs::copy(src, dst).map_err(wrap_io_err!(src, dst, "Copying to cache"))?;
+    }
+    Ok(())
+}
+
+/// Remove cached tarballs which are not in `keep` and were not used within `max_age`,
+/// and leftover temporary files. Returns the number of removed files and their total size.
+pub fn cache_prune(
+    cache_dir: &Path,
+    keep: &HashSet<String>,
+    max_age: Duration,
+) -> Result<(usize, u64)> {
+    let mut removed = (0, 0);
+    if !cache_dir.is_dir() {
+        return Ok(removed);
+    }
+    for entry in fs::read_dir(cache_dir).map_err(wrap_io_err!(cache_dir, "Reading cache dir"))? {
+        let entry = entry.map_err(wrap_io_err!(cache_dir, "Reading cache dir entry"))?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !path.is_file() || keep.contains(&name) {
+            continue;
+        }
+        let max_age = if name.ends_with(".tmp") {
+            max_age.max(TMP_MIN_AGE)
+        } else {
+            max_age
+        };
+        let metadata = entry
+            .metadata()
+            .map_err(wrap_io_err!(path, "Reading cache file metadata"))?;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .unwrap_or_default();
+        if age < max_age {
+            continue;
+        }
+        let size = metadata.len();
+        remove_all(&path)?;
+        removed.0 += 1;
+        removed.1 += size;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::fs;
+    use std::time::Duration;
+
+    use crate::cook::cache::{cache_prune, restore, store};
+    use crate::cook::fs::get_file_blake3;
+
+    #[test]
+    fn source_cache_roundtrip() {
+        let root = std::env::temp_dir().join("temp_test_dir_source_cache_roundtrip");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("recipe")).unwrap();
+        let cache_dir = root.join("cache");
+        let source_tar = root.join("recipe/source.tar");
+        fs::write(&source_tar, "tarball").unwrap();
+        let blake3 = get_file_blake3(&source_tar).unwrap();
+
+        store(&cache_dir, &blake3, &source_tar).unwrap();
+        fs::remove_file(&source_tar).unwrap();
+        assert!(restore(&cache_dir, &blake3, &source_tar).unwrap());
+        assert_eq!(fs::read_to_string(&source_tar).unwrap(), "tarball");
+        assert!(!restore(&cache_dir, "unknown", &root.join("other.tar")).unwrap());
+
+        // corrupted entries are dropped
+        fs::write(cache_dir.join("0000"), "corrupted").unwrap();
+        assert!(!restore(&cache_dir, "0000", &root.join("other.tar")).unwrap());
+        assert!(!cache_dir.join("0000").exists());
+
+        fs::write(cache_dir.join("1111"), "unused").unwrap();
+        // possibly still being written by another process
+        fs::write(cache_dir.join("2222.1.tmp"), "partial").unwrap();
+        let keep = HashSet::from([blake3.clone()]);
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert_eq!(cache_prune(&cache_dir, &keep, day).unwrap(), (0, 0));
+        assert_eq!(
+            cache_prune(&cache_dir, &keep, Duration::ZERO).unwrap(),
+            (1, 6)
+        );
+        assert!(cache_dir.join(&blake3).is_file());
+        assert!(cache_dir.join("2222.1.tmp").is_file());
+    }
+}
@@ -6,11 +6,14 @@ use pkgar_keys::PublicKeyFile;
 use crate::config::CookConfig;
 use crate::cook::fetch_repo;
 use crate::cook::package::{package_source_paths, package_target};
+use crate::cook::vendor::vendor_cargo_dir;
 use crate::cook::{fetch, fs, pty::PtyOut, script::*};
-use crate::recipe::{AutoDeps, BuildKind, CookRecipe, OptionalPackageRecipe, Recipe};
+use crate::recipe::{
+    AutoDeps, BuildKind, CookRecipe, OptionalPackageRecipe, Recipe, VariantSelection,
+};
 use std::io::Read;
 use std::{
-    collections::{BTreeSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     path::{Path, PathBuf},
     process::Command,
     str,
@@ -158,13 +161,14 @@ fn auto_deps_from_dynamic_linking(
 fn auto_deps_from_static_package_deps(
     build_dep_pkgars: &BTreeSet<(PackageName, PathBuf)>,
     dynamic_dep_pkgars: &BTreeSet<PackageName>,
+    variants: &VariantSelection,
 ) -> std::result::Result<BTreeSet<PackageName>, PackageError> {
     let static_dep_pkgars: Vec<PackageName> = build_dep_pkgars
         .iter()
         .map(|x| x.0.clone())
         .filter(|x| !dynamic_dep_pkgars.contains(x))
         .collect();
-    let pkgs = CookRecipe::get_package_deps_recursive(&static_dep_pkgars, false)?;
+    let pkgs = CookRecipe::get_package_deps_recursive(&static_dep_pkgars, false, variants)?;
 
     Ok(pkgs.into_iter().collect())
 }
@@ -199,6 +203,7 @@ pub fn build(
     target_dir: &Path,
     cook_recipe: &CookRecipe,
     cook_config: &CookConfig,
+    variants: &VariantSelection,
     logger: &PtyOut,
 ) -> Result<BuildResult> {
     let recipe = &cook_recipe.recipe;
@@ -207,6 +212,7 @@ pub fn build(
     let sysroot_dir = get_sub_target_dir(target_dir, "sysroot");
     let toolchain_dir = get_sub_target_dir(target_dir, "toolchain");
     let auto_deps_file = get_sub_target_dir(target_dir, "auto_deps.toml");
+    let build_env_file = get_sub_target_dir(target_dir, "build_env.toml");
     let stage_dirs = get_stage_dirs(&recipe.optional_packages, target_dir);
     let stage_pkgars: Vec<PathBuf> = stage_dirs
         .iter()
@@ -227,6 +233,7 @@ pub fn build(
         ]
         .concat(),
         false,
+        variants,
     )?;
     for dependency in build_deps.iter() {
         let (_, pkgar, _) = dependency.stage_paths();
@@ -245,6 +252,7 @@ pub fn build(
                 &stage_dirs,
                 $cached,
                 cook_config,
+                variants,
                 dep_pkgars,
                 logger,
             )
@@ -285,10 +293,11 @@ pub fn build(
         build_is_source_newer(
             logger,
             recipe_dir,
+            &recipe.parent_files,
             source_dir,
             &auto_deps_file,
             stage_pkgars,
-        )
+        ) || build_env_is_changed(logger, &build_env_file, &recipe.build.env)
     } {
         if auto_deps_file.is_file() {
             fs::remove_all(&auto_deps_file)?;
@@ -329,97 +338,7 @@ pub fn build(
             fs::create_dir_clean(&build_dir)?;
         }
 
-        let flags_fn = |name, flags: &Vec<String>| {
-            format!(
-                "{name}+=(\n{}\n)\n",
-                flags
-                    .iter()
-                    .map(|s| format!("  \"{s}\""))
-                    .collect::<Vec<String>>()
-                    .join("\n")
-            )
-        };
-
-        let bool_fn = |name, flag: &bool| {
-            if *flag { name } else { "" }
-        };
-
-        let mut allow_cargo_offline = false;
-        //TODO: better integration with redoxer (library instead of binary)
-        //TODO: configurable target
-        //TODO: Add more configurability, convert scripts to Rust?
-        let script = match &recipe.build.kind {
-            BuildKind::Cargo {
-                cargopath,
-                cargoflags,
-                cargopackages,
-                cargoexamples,
-                clearlocked,
-                cargopackagesprefixed,
-            } => {
-                allow_cargo_offline = true;
-                let mut script = format!(
-                    "DYNAMIC_INIT\n{}{}\nCOOKBOOK_CARGO_PATH={} ",
-                    bool_fn("COOKBOOK_CARGO_FLAGS=()\n", clearlocked),
-                    flags_fn("COOKBOOK_CARGO_FLAGS", cargoflags),
-                    cargopath.as_deref().unwrap_or(".")
-                );
-                if cargopackages.is_empty() && cargoexamples.is_empty() {
-                    script += "cookbook_cargo\n"
-                } else {
-                    if !cargopackages.is_empty() {
-                        script += if *cargopackagesprefixed {
-                            "cookbook_cargo_packages_prefixed"
-                        } else {
-                            "cookbook_cargo_packages"
-                        };
-                        for package in cargopackages {
-                            script += " ";
-                            script += package;
-                        }
-                        script += "\n";
-                    }
-                    if !cargoexamples.is_empty() {
-                        script += "cookbook_cargo_examples";
-                        for example in cargoexamples {
-                            script += " ";
-                            script += example;
-                        }
-                        script += "\n";
-                    }
-                }
-
-                script
-            }
-            BuildKind::Configure { configureflags } => format!(
-                "DYNAMIC_INIT\n{}cookbook_configure",
-                flags_fn("COOKBOOK_CONFIGURE_FLAGS", configureflags),
-            ),
-            BuildKind::Cmake { cmakeflags } => format!(
-                "DYNAMIC_INIT\n{}cookbook_cmake",
-                flags_fn("COOKBOOK_CMAKE_FLAGS", cmakeflags),
-            ),
-            BuildKind::Meson { mesonflags } => format!(
-                "DYNAMIC_INIT\n{}cookbook_meson",
-                flags_fn("COOKBOOK_MESON_FLAGS", mesonflags),
-            ),
-            BuildKind::Python {
-                pyprojectpath,
-                legacysetup,
-                pipflags,
-            } => format!(
-                "DYNAMIC_INIT\n{}{}{}cookbook_python",
-                bool_fn("COOKBOOK_PYTHON_LEGACY_SETUP=1\n", legacysetup),
-                pyprojectpath
-                    .as_ref()
-                    .map(|s| format!("COOKBOOK_PYTHON_PYPROJECT_PATH={s}\n"))
-                    .unwrap_or("".into()),
-                flags_fn("COOKBOOK_PYTHON_PIP_FLAGS", pipflags),
-            ),
-            BuildKind::Custom { script } => script.clone(),
-            BuildKind::Remote => unreachable!(),
-            BuildKind::None => "".to_owned(),
-        };
+        let (script, offline_deps) = build_template_script(&recipe.build.kind);
 
         let command = {
             //TODO: remove unwraps
@@ -464,8 +383,13 @@ pub fn build(
             if cook_config.verbose_cmd {
                 command.env("COOKBOOK_VERBOSE", "1");
             }
-            if cook_config.offline && allow_cargo_offline {
+            if cook_config.offline && offline_deps != OfflineDeps::None {
                 command.env("COOKBOOK_OFFLINE", "1");
+                if offline_deps == OfflineDeps::Cargo
+                    && let Some(cargo_vendor) = vendor_cargo_dir()
+                {
+                    command.env("COOKBOOK_CARGO_VENDOR", cargo_vendor);
+                }
             } else {
                 command.env_remove("COOKBOOK_OFFLINE");
             }
@@ -477,8 +401,12 @@ pub fn build(
         };
 
         let full_script = format!(
-            "{}\n{}\n{}\n{}",
-            BUILD_PRESCRIPT, SHARED_PRESCRIPT, script, BUILD_POSTSCRIPT
+            "{}\n{}\n{}{}\n{}",
+            BUILD_PRESCRIPT,
+            SHARED_PRESCRIPT,
+            build_env_script(&recipe.build.env)?,
+            script,
+            BUILD_POSTSCRIPT
         );
         fs::run_command_stdin(command, full_script.as_bytes(), logger)?;
 
@@ -487,16 +415,17 @@ pub fn build(
         for (i, feat) in recipe.optional_packages.iter().enumerate() {
             let stage_dir = &stage_dirs[i];
             fs::create_dir_clean(stage_dir)?;
-            for path in &feat.files {
-                let glob = globset::Glob::new(path).map_err(|e| format!("{}", e))?;
-                globs.push((glob.compile_matcher(), stage_dir.clone()));
-            }
+            globs.push((
+                build_globset(&feat.files)?,
+                build_globset(&feat.exclude)?,
+                stage_dir.clone(),
+            ));
         }
         fs::move_dir_all_fn(
             &stage_dir_tmp,
             &Box::new(|path: PathBuf| {
-                for (glob, dst) in &globs {
-                    if glob.is_match(&path) {
+                for (files, exclude, dst) in &globs {
+                    if files.is_match(&path) && !exclude.is_match(&path) {
                         return Some(dst.as_path());
                     }
                 }
@@ -507,6 +436,14 @@ pub fn build(
 
         // Move stage.tmp to stage atomically
         fs::rename(&stage_dir_tmp, stage_dir)?;
+
+        if recipe.build.env.is_empty() {
+            if build_env_file.is_file() {
+                fs::remove_all(&build_env_file)?;
+            }
+        } else {
+            fs::serialize_and_write(&build_env_file, &recipe.build.env)?;
+        }
     }
 
     if cook_config.clean_target {
@@ -525,6 +462,7 @@ pub fn build(
 fn build_is_source_newer(
     logger: &PtyOut,
     recipe_dir: &Path,
+    parent_files: &[PathBuf],
     source_dir: &Path,
     auto_deps_file: &Path,
     stage_pkgars: Vec<PathBuf>,
@@ -539,7 +477,11 @@ fn build_is_source_newer(
         return false;
     };
     let mut recipe_is_newest = false;
-    if let Ok(recipe_modified) = fs::modified(&recipe_dir.join("recipe.toml"))
+    // recipe files merged by "extends" count as part of the recipe
+    let recipe_files: Vec<PathBuf> = std::iter::once(recipe_dir.join("recipe.toml"))
+        .chain(parent_files.iter().cloned())
+        .collect();
+    if let Ok(recipe_modified) = fs::modified_all(&recipe_files, fs::modified)
         && recipe_modified > source_modified
     {
         source_modified = recipe_modified;
@@ -556,6 +498,238 @@ fn build_is_source_newer(
     newer
 }
 
+fn build_globset(patterns: &[String]) -> Result<globset::GlobSet> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(globset::Glob::new(pattern).map_err(|e| format!("{}", e))?);
+    }
+    builder.build().map_err(|e| Error::from(format!("{}", e)))
+}
+
+/// Whether `[build.env]` is different from the one used in the last build
+fn build_env_is_changed(
+    logger: &PtyOut,
+    build_env_file: &Path,
+    env: &BTreeMap<String, String>,
+) -> bool {
+    let last_env: BTreeMap<String, String> = if build_env_file.is_file() {
+        match fs::read_toml(build_env_file) {
+            Ok(last_env) => last_env,
+            Err(_) => return true,
+        }
+    } else {
+        BTreeMap::new()
+    };
+    let changed = last_env != *env;
+    if changed {
+        log_to_pty!(logger, "DEBUG: updating build: build env is changed");
+    }
+    changed
+}
+
+/// Which dependencies of a build template can be provided to an offline build
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OfflineDeps {
+    None,
+    /// Crates fetched by cargo, or vendored by "repo vendor"
+    Cargo,
+    /// Modules vendored into the source
+    Go,
+}
+
+/// Generates the script of a build template, and which of its dependencies can be offline
+fn build_template_script(kind: &BuildKind) -> (String, OfflineDeps) {
+    let flags_fn = |name, flags: &Vec<String>| {
+        format!(
+            "{name}+=(\n{}\n)\n",
+            flags
+                .iter()
+                .map(|s| format!("  \"{s}\""))
+                .collect::<Vec<String>>()
+                .join("\n")
+        )
+    };
+
+    let bool_fn = |name, flag: &bool| {
+        if *flag { name } else { "" }
+    };
+
+    let mut offline_deps = OfflineDeps::None;
+    //TODO: better integration with redoxer (library instead of binary)
+    //TODO: configurable target
+    //TODO: Add more configurability, convert scripts to Rust?
+    let script = match kind {
+        BuildKind::Cargo {
+            cargopath,
+            cargoflags,
+            cargopackages,
+            cargoexamples,
+            clearlocked,
+            cargopackagesprefixed,
+        } => {
+            offline_deps = OfflineDeps::Cargo;
+            let mut script = format!(
+                "DYNAMIC_INIT\n{}{}\nCOOKBOOK_CARGO_PATH={} ",
+                bool_fn("COOKBOOK_CARGO_FLAGS=()\n", clearlocked),
+                flags_fn("COOKBOOK_CARGO_FLAGS", cargoflags),
+                cargopath.as_deref().unwrap_or(".")
+            );
+            if cargopackages.is_empty() && cargoexamples.is_empty() {
+                script += "cookbook_cargo\n"
+            } else {
+                if !cargopackages.is_empty() {
+                    script += if *cargopackagesprefixed {
+                        "cookbook_cargo_packages_prefixed"
+                    } else {
+                        "cookbook_cargo_packages"
+                    };
+                    for package in cargopackages {
+                        script += " ";
+                        script += package;
+                    }
+                    script += "\n";
+                }
+                if !cargoexamples.is_empty() {
+                    script += "cookbook_cargo_examples";
+                    for example in cargoexamples {
+                        script += " ";
+                        script += example;
+                    }
+                    script += "\n";
+                }
+            }
+
+            script
+        }
+        BuildKind::CargoC {
+            cargopath,
+            cargoflags,
+            librarytype,
+        } => {
+            offline_deps = OfflineDeps::Cargo;
+            format!(
+                "DYNAMIC_INIT\n{}{}COOKBOOK_CARGO_PATH=\"{}\"\ncookbook_cargo_c",
+                flags_fn("COOKBOOK_CARGO_FLAGS", cargoflags),
+                librarytype
+                    .as_ref()
+                    .map(|s| format!("COOKBOOK_CARGO_C_LIBRARY_TYPE=\"{}\"\n", shell_escape(s)))
+                    .unwrap_or("".into()),
+                shell_escape(cargopath.as_deref().unwrap_or(".")),
+            )
+        }
+        BuildKind::Configure { configureflags } => format!(
+            "DYNAMIC_INIT\n{}cookbook_configure",
+            flags_fn("COOKBOOK_CONFIGURE_FLAGS", configureflags),
+        ),
+        BuildKind::Cmake { cmakeflags } => format!(
+            "DYNAMIC_INIT\n{}cookbook_cmake",
+            flags_fn("COOKBOOK_CMAKE_FLAGS", cmakeflags),
+        ),
+        BuildKind::Meson { mesonflags } => format!(
+            "DYNAMIC_INIT\n{}cookbook_meson",
+            flags_fn("COOKBOOK_MESON_FLAGS", mesonflags),
+        ),
+        BuildKind::Make {
+            makepath,
+            makeflags,
+            install_target,
+        } => format!(
+            "DYNAMIC_INIT\n{}{}{}cookbook_make",
+            makepath
+                .as_ref()
+                .map(|s| format!("COOKBOOK_MAKE_PATH=\"{}\"\n", shell_escape(s)))
+                .unwrap_or("".into()),
+            install_target
+                .as_ref()
+                .map(|s| format!("COOKBOOK_MAKE_INSTALL_TARGET=\"{}\"\n", shell_escape(s)))
+                .unwrap_or("".into()),
+            flags_fn("COOKBOOK_MAKE_FLAGS", makeflags),
+        ),
+        BuildKind::Go {
+            gopackages,
+            goflags,
+            ldflags,
+        } => {
+            offline_deps = OfflineDeps::Go;
+            let mut script = format!(
+                "DYNAMIC_INIT\n{}{}cookbook_go",
+                flags_fn("COOKBOOK_GO_FLAGS", goflags),
+                flags_fn("COOKBOOK_GO_LDFLAGS", ldflags),
+            );
+            for package in gopackages {
+                script += &format!(" \"{}\"", shell_escape(package));
+            }
+            script
+        }
+        BuildKind::Python {
+            pyprojectpath,
+            legacysetup,
+            pipflags,
+        } => format!(
+            "DYNAMIC_INIT\n{}{}{}cookbook_python",
+            bool_fn("COOKBOOK_PYTHON_LEGACY_SETUP=1\n", legacysetup),
+            pyprojectpath
+                .as_ref()
+                .map(|s| format!("COOKBOOK_PYTHON_PYPROJECT_PATH=\"{}\"\n", shell_escape(s)))
+                .unwrap_or("".into()),
+            flags_fn("COOKBOOK_PYTHON_PIP_FLAGS", pipflags),
+        ),
+        BuildKind::Custom { script } => script.clone(),
+        BuildKind::Remote => unreachable!(),
+        BuildKind::None => "".to_owned(),
+    };
+    (script, offline_deps)
+}
+
+/// Escapes a string to be put inside double quotes in the build script
+fn shell_escape(s: &str) -> String {
+    s.chars().fold(String::new(), |mut acc, c| {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            acc.push('\\');
+        }
+        acc.push(c);
+        acc
+    })
+}
+
+/// Convert `[build.env]` into bash exports, expanding only `COOKBOOK_*` variables
+fn build_env_script(env: &BTreeMap<String, String>) -> Result<String> {
+    let var_regex = regex::Regex::new(r"\$(?:\{(COOKBOOK_\w+)\}|(COOKBOOK_\w+))").unwrap();
+    let mut script = String::new();
+    for (key, value) in env {
+        if key.is_empty()
+            || key.starts_with(|c: char| c.is_ascii_digit())
+            || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(Error::Other(format!("Invalid build env name {key:?}")));
+        }
+        let mut last = 0;
+        let mut expanded = String::new();
+        for caps in var_regex.captures_iter(value) {
+            let all = caps.get(0).unwrap();
+            let var = caps.get(1).or(caps.get(2)).unwrap();
+            expanded += &shell_escape(&value[last..all.start()]);
+            expanded += &format!("${{{}}}", var.as_str());
+            last = all.end();
+        }
+        expanded += &shell_escape(&value[last..]);
+        script += &match key.as_str() {
+            // The prescripts already set these up, so prepend instead of replacing them
+            "CPPFLAGS" => format!("export {key}=\"{expanded}${{{key}:+ ${key}}}\"\n"),
+            // DYNAMIC_INIT sets these up again from the USER_ variables
+            "LDFLAGS" | "RUSTFLAGS" => format!(
+                "USER_{key}=\"${{USER_{key}}}{expanded} \"\n\
+                 export {key}=\"{expanded}${{{key}:+ ${key}}}\"\n"
+            ),
+            _ => format!("export {key}=\"{expanded}\"\n"),
+        };
+    }
+    if !script.is_empty() {
+        script += "reexport_flags\n";
+    }
+    Ok(script)
+}
+
 pub fn remove_stage_dir(stage_dir: &PathBuf) -> crate::Result<()> {
     if stage_dir.is_dir() {
         fs::remove_all(stage_dir)?;
@@ -705,6 +879,7 @@ fn build_auto_deps(
     stage_dirs: &Vec<PathBuf>,
     cached: bool,
     cook_config: &CookConfig,
+    variants: &VariantSelection,
     mut dep_pkgars: BTreeSet<(PackageName, PathBuf)>,
     logger: &PtyOut,
 ) -> Result<BTreeSet<PackageName>> {
@@ -721,8 +896,8 @@ fn build_auto_deps(
     } else {
         let mut dynamic_deps = auto_deps_from_dynamic_linking(stage_dirs, &dep_pkgars, logger);
         dep_pkgars.retain(|x| recipe.build.dependencies.contains(&x.0));
-        let package_deps =
-            auto_deps_from_static_package_deps(&dep_pkgars, &dynamic_deps).unwrap_or_default();
+        let package_deps = auto_deps_from_static_package_deps(&dep_pkgars, &dynamic_deps, variants)
+            .unwrap_or_default();
         dynamic_deps.extend(package_deps);
 
         let wrapper = AutoDeps {
@@ -819,8 +994,138 @@ fn hint_incorrect_pkey(logger: &PtyOut, source_pubkey: &Path, source_pkgar: &Pat
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::os::unix;
 
+    #[test]
+    fn build_env_script() {
+        let env = BTreeMap::from([
+            (
+                "CFLAGS".to_string(),
+                "-I${COOKBOOK_SYSROOT}/include -DNAME=\"$HOME\"".to_string(),
+            ),
+            ("PKG_DIR".to_string(), "$COOKBOOK_STAGE/usr".to_string()),
+        ]);
+        assert_eq!(
+            super::build_env_script(&env).unwrap(),
+            "export CFLAGS=\"-I${COOKBOOK_SYSROOT}/include -DNAME=\\\"\\$HOME\\\"\"\n\
+             export PKG_DIR=\"${COOKBOOK_STAGE}/usr\"\n\
+             reexport_flags\n"
+        );
+
+        let env = BTreeMap::from([("NOT-VALID".to_string(), "1".to_string())]);
+        assert!(super::build_env_script(&env).is_err());
+    }
+
+    #[test]
+    fn build_env_flags_on_template() {
+        use crate::cook::script::{BUILD_PRESCRIPT, SHARED_PRESCRIPT};
+        use crate::recipe::BuildKind;
+        use std::process::Command;
+
+        let env = BTreeMap::from([
+            ("LDFLAGS".to_string(), "-lfoo".to_string()),
+            ("RUSTFLAGS".to_string(), "--cfg foo".to_string()),
+        ]);
+        let (template, _) = super::build_template_script(&BuildKind::Configure {
+            configureflags: Vec::new(),
+        });
+        let script = format!(
+            "{BUILD_PRESCRIPT}\n{SHARED_PRESCRIPT}\n{}\
+             function cookbook_configure {{ echo \"$LDFLAGS|$RUSTFLAGS\"; }}\n{template}\n",
+            super::build_env_script(&env).unwrap()
+        );
+        let output = Command::new("bash")
+            .arg("-ec")
+            .arg(script)
+            .env("TARGET", "x86_64-unknown-redox")
+            .env("COOKBOOK_SYSROOT", "/sysroot")
+            .env_remove("LDFLAGS")
+            .env_remove("RUSTFLAGS")
+            .env_remove("CPPFLAGS")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "-lfoo -Wl,-rpath-link,/sysroot/lib -L/sysroot/lib -Wl,--export-dynamic|\
+             --cfg foo -C target-feature=-crt-static -L native=/sysroot/lib \
+             -C link-arg=-Wl,-rpath-link,/sysroot/lib\n"
+        );
+    }
+
+    #[test]
+    fn cargo_c_template() {
+        use crate::recipe::BuildKind;
+
+        let (script, offline_deps) = super::build_template_script(&BuildKind::CargoC {
+            cargopath: Some("capi".to_string()),
+            cargoflags: vec!["--features=capi".to_string()],
+            librarytype: Some("cdylib; rm -rf $HOME".to_string()),
+        });
+        assert_eq!(offline_deps, super::OfflineDeps::Cargo);
+        assert_eq!(
+            script,
+            "DYNAMIC_INIT\n\
+             COOKBOOK_CARGO_FLAGS+=(\n  \"--features=capi\"\n)\n\
+             COOKBOOK_CARGO_C_LIBRARY_TYPE=\"cdylib; rm -rf \\$HOME\"\n\
+             COOKBOOK_CARGO_PATH=\"capi\"\n\
+             cookbook_cargo_c"
+        );
+    }
+
+    #[test]
+    fn make_template() {
+        use crate::recipe::BuildKind;
+
+        let (script, offline_deps) = super::build_template_script(&BuildKind::Make {
+            makepath: Some("src dir".to_string()),
+            makeflags: vec!["NO_X11=1".to_string()],
+            install_target: Some("install-$(BIN)".to_string()),
+        });
+        assert_eq!(offline_deps, super::OfflineDeps::None);
+        assert_eq!(
+            script,
+            "DYNAMIC_INIT\n\
+             COOKBOOK_MAKE_PATH=\"src dir\"\n\
+             COOKBOOK_MAKE_INSTALL_TARGET=\"install-\\$(BIN)\"\n\
+             COOKBOOK_MAKE_FLAGS+=(\n  \"NO_X11=1\"\n)\n\
+             cookbook_make"
+        );
+
+        let (script, _) = super::build_template_script(&BuildKind::Python {
+            pyprojectpath: Some("python/`pwd`".to_string()),
+            legacysetup: false,
+            pipflags: vec![],
+        });
+        assert_eq!(
+            script,
+            "DYNAMIC_INIT\n\
+             COOKBOOK_PYTHON_PYPROJECT_PATH=\"python/\\`pwd\\`\"\n\
+             COOKBOOK_PYTHON_PIP_FLAGS+=(\n\n)\n\
+             cookbook_python"
+        );
+    }
+
+    #[test]
+    fn go_template() {
+        use crate::recipe::BuildKind;
+
+        let (script, offline_deps) = super::build_template_script(&BuildKind::Go {
+            gopackages: vec!["./cmd/foo".to_string(), "./cmd/my tool".to_string()],
+            goflags: vec!["-trimpath".to_string()],
+            ldflags: vec!["-s".to_string()],
+        });
+        assert_eq!(offline_deps, super::OfflineDeps::Go);
+        assert_eq!(
+            script,
+            "DYNAMIC_INIT\n\
+             COOKBOOK_GO_FLAGS+=(\n  \"-trimpath\"\n)\n\
+             COOKBOOK_GO_LDFLAGS+=(\n  \"-s\"\n)\n\
+             cookbook_go \"./cmd/foo\" \"./cmd/my tool\""
+        );
+    }
+
     #[test]
     fn file_system_loop_no_infinite_loop() {
         let mut root = std::env::temp_dir();
@@ -0,0 +1,81 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::cook::fs;
+use crate::cook::package::package_target;
+use crate::cook::pty::PtyOut;
+use crate::recipe::CookRecipe;
+use crate::{Error, Result, is_redox, wrap_io_err};
+
+/// Run the `[test]` script of a recipe against its stage dir, returns false if there's no test.
+///
+/// The stage is copied to `target/<target>/test` first, so tests can't modify the package.
+/// Host recipes are tested natively with the stage on `PATH`, while Redox targets are tested
+/// with `cookbook_redoxer write-exec`, where the stage copy is the root filesystem.
+pub fn test(recipe: &CookRecipe, logger: &PtyOut) -> Result<bool> {
+    let Some(test) = &recipe.recipe.test else {
+        return Ok(false);
+    };
+    let stage_dir = recipe.stage_paths().0;
+    if !stage_dir.is_dir() {
+        return Err(Error::Other(format!(
+            "Stage dir {} is missing, the recipe has to be built without COOKBOOK_CLEAN_TARGET",
+            stage_dir.display()
+        )));
+    }
+
+    let test_dir = recipe.target_dir().join("test");
+    fs::create_dir_clean(&test_dir)?;
+    let mut command = Command::new("cp");
+    command.arg("-a").arg(stage_dir.join(".")).arg(&test_dir);
+    fs::run_command(command, logger)?;
+    let test_dir = test_dir
+        .canonicalize()
+        .map_err(wrap_io_err!(test_dir, "Canonicalizing test dir"))?;
+
+    let native = recipe.name.is_host() || is_redox();
+    let mut command = if native {
+        let mut command = Command::new("bash");
+        command.arg("-e");
+        command.current_dir(&test_dir);
+        command.env("PATH", prepend_path(&test_dir.join("usr/bin"), "PATH"));
+        command.env(
+            "LD_LIBRARY_PATH",
+            prepend_path(&test_dir.join("usr/lib"), "LD_LIBRARY_PATH"),
+        );
+        command
+    } else {
+        let local_redoxer = Path::new("target/release/cookbook_redoxer");
+        let cookbook_redoxer = local_redoxer
+            .canonicalize()
+            .unwrap_or(PathBuf::from("/bin/false"));
+        let mut command = Command::new(&cookbook_redoxer);
+        command.arg("write-exec").arg("sh").arg("-e").arg("-c");
+        command.arg(&test.script);
+        command
+    };
+    command.env("TARGET", package_target(&recipe.name));
+    command.env("COOKBOOK_NAME", recipe.name.name());
+    command.env("COOKBOOK_STAGE", &test_dir);
+    if let Ok(cookbook_recipe) = recipe.dir.canonicalize() {
+        command.env("COOKBOOK_RECIPE", cookbook_recipe);
+    }
+
+    if native {
+        fs::run_command_stdin(command, test.script.as_bytes(), logger)?;
+    } else {
+        fs::run_command(command, logger)?;
+    }
+    fs::remove_all(&test_dir)?;
+    Ok(true)
+}
+
+fn prepend_path(dir: &Path, var: &str) -> String {
+    match env::var(var) {
+        Ok(value) if !value.is_empty() => format!("{}:{}", dir.display(), value),
+        _ => dir.display().to_string(),
+    }
+}
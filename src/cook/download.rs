@@ -0,0 +1,468 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use curl::easy::{Easy, Easy2, Handler, WriteError};
+use pkg::callback::{Callback, PlainCallback};
+use sha2::Digest;
+
+use crate::{
+    Error, Result, bail_other_err,
+    config::translate_mirror,
+    cook::{
+        fetch_repo::PlainPtyCallback,
+        fs::{remove_all, rename},
+        pty::PtyOut,
+    },
+    log_to_pty, wrap_io_err,
+};
+
+// This file contains the downloader of source tarballs, built on libcurl.
+
+/// How many times a failed download is retried, waiting twice as long each time
+const RETRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Abort transfers slower than 1 byte per second for this long, so they can be retried
+const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The checksums a download is expected to have
+#[derive(Clone, Copy)]
+pub struct Checksums<'a> {
+    pub blake3: &'a Option<String>,
+    pub sha256: &'a Option<String>,
+    pub sha512: &'a Option<String>,
+}
+
+impl Checksums<'_> {
+    pub const NONE: Checksums<'static> = Checksums {
+        blake3: &None,
+        sha256: &None,
+        sha512: &None,
+    };
+}
+
+/// Hashers for every checksum configured in [`Checksums`]
+struct Hashers {
+    blake3: Option<blake3::Hasher>,
+    sha256: Option<sha2::Sha256>,
+    sha512: Option<sha2::Sha512>,
+}
+
+impl Hashers {
+    fn new(checksums: Checksums) -> Self {
+        Hashers {
+            blake3: checksums.blake3.as_ref().map(|_| blake3::Hasher::new()),
+            sha256: checksums.sha256.as_ref().map(|_| sha2::Sha256::new()),
+            sha512: checksums.sha512.as_ref().map(|_| sha2::Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        if let Some(hasher) = &mut self.blake3 {
+            hasher.update(data);
+        }
+        if let Some(hasher) = &mut self.sha256 {
+            hasher.update(data);
+        }
+        if let Some(hasher) = &mut self.sha512 {
+            hasher.update(data);
+        }
+    }
+
+    /// Returns the kind and value of the first checksum that doesn't match
+    fn verify(self, checksums: Checksums) -> Option<(&'static str, String)> {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let sums = [
+            (
+                "blake3",
+                checksums.blake3,
+                self.blake3.map(|h| h.finalize().to_hex().to_string()),
+            ),
+            (
+                "sha256",
+                checksums.sha256,
+                self.sha256.map(|h| hex(&h.finalize())),
+            ),
+            (
+                "sha512",
+                checksums.sha512,
+                self.sha512.map(|h| hex(&h.finalize())),
+            ),
+        ];
+        sums.into_iter()
+            .find_map(|(kind, expected, sum)| match (expected, sum) {
+                (Some(expected), Some(sum)) if !sum.eq_ignore_ascii_case(expected) => {
+                    Some((kind, sum))
+                }
+                _ => None,
+            })
+    }
+}
+
+impl Write for Hashers {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.update(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Attempt {
+    Done(Box<Hashers>),
+    Retry(Error),
+}
+
+/// What to do with a response, given its status code and the length of the partial download
+#[derive(Debug, PartialEq)]
+enum Response {
+    /// Append the body to the partial download
+    Resume,
+    /// The server sent the whole file, replace the partial download with it
+    Restart,
+    /// The partial download is the whole file already
+    Complete,
+    Retry,
+    Fail,
+}
+
+impl Response {
+    /// `code` is 0 for protocols without status codes, such as file://
+    fn new(code: u32, offset: u64) -> Self {
+        match code {
+            200 if offset > 0 => Response::Restart,
+            0 | 200..300 => Response::Resume,
+            416 if offset > 0 => Response::Complete,
+            408 | 429 | 500.. => Response::Retry,
+            _ => Response::Fail,
+        }
+    }
+}
+
+/// How long to wait before retrying a download for the `retries`th time
+fn retry_delay(retries: u32) -> Duration {
+    RETRY_DELAY * 2u32.pow(retries)
+}
+
+/// Download `url` into `dest` if it's not there yet, supporting http(s):// and file:// URLs.
+///
+/// Data is written to `dest` with a ".tmp" suffix first, which is resumed by later calls
+/// if the download is interrupted. Every checksum in `checksums` is verified while the file
+/// is downloaded. A mismatching file is removed; if it was resumed from an earlier attempt,
+/// it is downloaded once more from scratch before giving up.
+pub fn download(url: &str, dest: &Path, checksums: Checksums, logger: &PtyOut) -> Result<()> {
+    if dest.is_file() {
+        return Ok(());
+    }
+    let dest_tmp = PathBuf::from(format!("{}.tmp", dest.display()));
+    let mut resumed = dest_tmp.is_file();
+    loop {
+        let hashers = download_with_retries(url, &dest_tmp, checksums, logger)?;
+        let Some((kind, sum)) = hashers.verify(checksums) else {
+            return rename(&dest_tmp, dest);
+        };
+        remove_all(&dest_tmp)?;
+        if !resumed {
+            bail_other_err!("The downloaded {url} has {kind} {sum:?}, which is not expected");
+        }
+        log_to_pty!(
+            logger,
+            "WARNING: the resumed download of {url} has a wrong {kind}, downloading again"
+        );
+        resumed = false;
+    }
+}
+
+fn download_with_retries(
+    url: &str,
+    dest_tmp: &Path,
+    checksums: Checksums,
+    logger: &PtyOut,
+) -> Result<Hashers> {
+    let url = translate_mirror(url);
+    let mut retries = 0;
+    loop {
+        match transfer(&url, dest_tmp, checksums, logger)? {
+            Attempt::Done(hashers) => return Ok(*hashers),
+            Attempt::Retry(e) if retries < RETRIES => {
+                let delay = retry_delay(retries);
+                retries += 1;
+                log_to_pty!(
+                    logger,
+                    "WARNING: {e}, retrying in {}s ({retries}/{RETRIES})",
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+            }
+            Attempt::Retry(e) => return Err(e),
+        }
+    }
+}
+
+/// Receives a transfer, writing the body into the partial download and the hashers
+struct Receiver<'a> {
+    file: fs::File,
+    hashers: Hashers,
+    checksums: Checksums<'a>,
+    /// Length of the partial download when the transfer started
+    offset: u64,
+    file_name: String,
+    callback: Box<dyn Callback>,
+    /// Status code and content length of the latest response
+    code: u32,
+    length: Option<u64>,
+    /// Whether the body is being written, decided on the first chunk of it
+    writing: Option<bool>,
+    progress: bool,
+    error: Option<io::Error>,
+}
+
+impl Receiver<'_> {
+    fn start(&mut self) -> io::Result<bool> {
+        match Response::new(self.code, self.offset) {
+            Response::Restart => {
+                // the server can't resume, start over with this response
+                self.file.set_len(0)?;
+                self.hashers = Hashers::new(self.checksums);
+                self.offset = 0;
+            }
+            Response::Resume => {}
+            _ => return Ok(false),
+        }
+        if let Some(length) = self.length.filter(|_| self.code != 0) {
+            self.callback
+                .download_start(self.offset + length, &self.file_name);
+            self.callback.download_increment(self.offset);
+            self.progress = true;
+        }
+        Ok(true)
+    }
+}
+
+impl Handler for Receiver<'_> {
+    fn write(&mut self, data: &[u8]) -> std::result::Result<usize, WriteError> {
+        let writing = match self.writing {
+            Some(writing) => writing,
+            None => match self.start() {
+                Ok(writing) => *self.writing.insert(writing),
+                Err(e) => {
+                    self.error = Some(e);
+                    return Ok(0);
+                }
+            },
+        };
+        if !writing {
+            // the body of an error response
+            return Ok(data.len());
+        }
+        if let Err(e) = self.file.write_all(data) {
+            self.error = Some(e);
+            return Ok(0);
+        }
+        self.hashers.update(data);
+        if self.progress {
+            self.callback.download_increment(data.len() as u64);
+        }
+        Ok(data.len())
+    }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        let line = String::from_utf8_lossy(data);
+        if line.starts_with("HTTP/") {
+            // a new response, after a redirect or an informational one
+            self.code = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse().ok())
+                .unwrap_or(0);
+            self.length = None;
+        } else if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            self.length = value.trim().parse().ok();
+        }
+        true
+    }
+}
+
+/// Download into `dest_tmp`, resuming from its current length
+fn transfer(url: &str, dest_tmp: &Path, checksums: Checksums, logger: &PtyOut) -> Result<Attempt> {
+    let curl_err = |e: curl::Error| Error::Other(format!("Downloading {url}: {e}"));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(dest_tmp)
+        .map_err(wrap_io_err!(dest_tmp, "Opening download"))?;
+    // hash what was downloaded previously, then continue with the incoming data
+    let mut hashers = Hashers::new(checksums);
+    let offset =
+        io::copy(&mut file, &mut hashers).map_err(wrap_io_err!(dest_tmp, "Reading download"))?;
+
+    let callback: Box<dyn Callback> = match logger {
+        Some((_, stderr)) => Box::new(PlainPtyCallback::new(
+            stderr.try_clone().map_err(wrap_io_err!("Cloning pty"))?,
+        )),
+        None => Box::new(PlainCallback::new()),
+    };
+    let mut easy = Easy2::new(Receiver {
+        file,
+        hashers,
+        checksums,
+        offset,
+        file_name: url.rsplit('/').next().unwrap_or(url).to_string(),
+        callback,
+        code: 0,
+        length: None,
+        writing: None,
+        progress: false,
+        error: None,
+    });
+    easy.url(url).map_err(curl_err)?;
+    easy.follow_location(true).map_err(curl_err)?;
+    easy.useragent("curl/redox-cookbook").map_err(curl_err)?;
+    easy.connect_timeout(CONNECT_TIMEOUT).map_err(curl_err)?;
+    easy.low_speed_limit(1).map_err(curl_err)?;
+    easy.low_speed_time(STALL_TIMEOUT).map_err(curl_err)?;
+    easy.resume_from(offset).map_err(curl_err)?;
+    let performed = easy.perform();
+
+    let code = easy.response_code().map_err(curl_err)?;
+    let receiver = easy.get_mut();
+    if receiver.progress {
+        receiver.callback.download_end();
+    }
+    if let Some(e) = receiver.error.take() {
+        return Err(Error::from_io_error(e, "Writing download"));
+    }
+    if let Err(e) = performed {
+        if e.is_file_couldnt_read_file() {
+            return Err(curl_err(e));
+        }
+        if e.is_range_error() {
+            // the server can't resume, start over
+            receiver
+                .file
+                .set_len(0)
+                .map_err(wrap_io_err!(dest_tmp, "Truncating download"))?;
+        }
+        return Ok(Attempt::Retry(curl_err(e)));
+    }
+    let hashers = std::mem::replace(&mut receiver.hashers, Hashers::new(checksums));
+    match Response::new(code, receiver.offset) {
+        Response::Resume | Response::Complete => Ok(Attempt::Done(Box::new(hashers))),
+        Response::Restart => {
+            // an empty response to a resumed download
+            receiver
+                .file
+                .set_len(0)
+                .map_err(wrap_io_err!(dest_tmp, "Truncating download"))?;
+            Ok(Attempt::Retry(Error::Other(format!(
+                "Downloading {url}: the server can't resume"
+            ))))
+        }
+        Response::Retry => Ok(Attempt::Retry(Error::Other(format!(
+            "Downloading {url}: HTTP status {code}"
+        )))),
+        Response::Fail => Err(Error::Other(format!(
+            "Downloading {url}: HTTP status {code}"
+        ))),
+    }
+}
+
+/// Download a small text file, such as a directory listing
+pub fn download_string(url: &str) -> Result<String> {
+    let curl_err = |e: curl::Error| Error::Other(format!("Downloading {url}: {e}"));
+    let mut data = Vec::new();
+    let mut easy = Easy::new();
+    easy.url(url).map_err(curl_err)?;
+    easy.follow_location(true).map_err(curl_err)?;
+    easy.fail_on_error(true).map_err(curl_err)?;
+    easy.useragent("curl/redox-cookbook").map_err(curl_err)?;
+    easy.connect_timeout(CONNECT_TIMEOUT).map_err(curl_err)?;
+    {
+        let mut transfer = easy.transfer();
+        transfer
+            .write_function(|chunk| {
+                data.extend_from_slice(chunk);
+                Ok(chunk.len())
+            })
+            .map_err(curl_err)?;
+        transfer.perform().map_err(curl_err)?;
+    }
+    Ok(String::from_utf8_lossy(&data).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::cook::download::{Checksums, Hashers, Response, download, retry_delay};
+    use crate::cook::fs::get_file_blake3;
+
+    #[test]
+    fn download_response() {
+        assert_eq!(Response::new(200, 0), Response::Resume);
+        assert_eq!(Response::new(206, 500), Response::Resume);
+        assert_eq!(Response::new(0, 500), Response::Resume);
+        assert_eq!(Response::new(200, 500), Response::Restart);
+        assert_eq!(Response::new(416, 500), Response::Complete);
+        assert_eq!(Response::new(416, 0), Response::Fail);
+        assert_eq!(Response::new(404, 0), Response::Fail);
+        assert_eq!(Response::new(429, 0), Response::Retry);
+        assert_eq!(Response::new(503, 500), Response::Retry);
+        assert_eq!(retry_delay(0), Duration::from_secs(1));
+        assert_eq!(retry_delay(3), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn download_checksums() {
+        let sha256 =
+            Some("9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08".to_string());
+        let checksums = Checksums {
+            sha256: &sha256,
+            ..Checksums::NONE
+        };
+        let mut hashers = Hashers::new(checksums);
+        hashers.update(b"te");
+        hashers.update(b"st");
+        assert_eq!(hashers.verify(checksums), None);
+
+        let mut hashers = Hashers::new(checksums);
+        hashers.update(b"tset");
+        assert_eq!(
+            hashers.verify(checksums).map(|(kind, _)| kind),
+            Some("sha256")
+        );
+    }
+
+    #[test]
+    fn download_resumes_file() {
+        let root = std::env::temp_dir().join("temp_test_dir_download_resumes");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let body = "0123456789".repeat(100);
+        std::fs::write(root.join("expected"), &body).unwrap();
+        let blake3 = Some(get_file_blake3(&root.join("expected")).unwrap());
+        // an interrupted download
+        std::fs::write(root.join("source.tar.tmp"), &body[..500]).unwrap();
+
+        crate::config::init_config();
+        let dest = root.join("source.tar");
+        let url = format!("file://{}", root.join("expected").display());
+        let checksums = Checksums {
+            blake3: &blake3,
+            ..Checksums::NONE
+        };
+        download(&url, &dest, checksums, &None).unwrap();
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), body);
+        assert!(!root.join("source.tar.tmp").exists());
+    }
+}
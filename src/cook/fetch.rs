@@ -1,15 +1,20 @@
 use crate::cook::{
+    archive::{ArchiveFormat, extract_7z, extract_zip},
+    cache::{cache_restore, cache_store},
+    download::{Checksums, download},
     fetch_repo::{self, PlainPtyCallback},
     fs::*,
+    git_cache::{git_add_alternate, git_cache_dir, git_mirror},
     package::{get_package_name, package_source_paths},
     pty::PtyOut,
     script::*,
+    vendor::{vendor_restore_extra_git, vendor_restore_git, vendor_restore_package},
 };
 use crate::{
     Error, Result, bail_other_err,
     config::translate_mirror,
     is_redox, log_to_pty,
-    recipe::{BuildKind, CookRecipe, SourceRecipe},
+    recipe::{BuildKind, CookRecipe, ExtraSource, ExtraSourceRecipe, SourceRecipe, split_rule},
     wrap_io_err, wrap_other_err,
 };
 use pkg::{SourceIdentifier, net_backend::DownloadBackendWriter};
@@ -18,7 +23,7 @@ use std::{
     collections::BTreeMap,
     fs::{self, File},
     io::Read,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     process::Command,
     rc::Rc,
 };
@@ -89,43 +94,76 @@ pub fn fetch_offline(recipe: &CookRecipe, logger: &PtyOut) -> Result<FetchResult
             r
         }
         Some(SourceRecipe::Git {
-            git: _,
+            git,
             upstream: _,
             branch: _,
+            tag: _,
             rev: _,
             patches,
             script,
             shallow_clone: _,
+            extra,
         }) => {
+            if !source_dir.is_dir() {
+                let source_dir_tmp = recipe_dir.join("source.tmp");
+                if source_dir_tmp.exists() {
+                    remove_all(&source_dir_tmp)?;
+                }
+                if vendor_restore_git(recipe, git, &source_dir_tmp, logger)? {
+                    vendor_restore_extra_git(recipe, &source_dir_tmp, extra, logger)?;
+                    fetch_extra_sources(recipe_dir, &source_dir_tmp, extra, true, logger)?;
+                    fetch_apply_patches(recipe_dir, patches, script, &source_dir_tmp, logger)?;
+                    rename(&source_dir_tmp, &source_dir)?;
+                }
+            }
             offline_check_exists(&source_dir)?;
+            for e in extra {
+                offline_check_exists(&source_dir.join(&e.dir))?;
+            }
             let (head_rev, _) = get_git_head_rev(&source_dir)?;
             let patches_blake3 = get_patches_blake3(recipe_dir, patches, script)?;
-            FetchResult::cached(source_dir, head_rev, patches_blake3)
+            let ident = get_extra_source_ident(head_rev, extra);
+            FetchResult::cached(source_dir, ident, patches_blake3)
         }
         Some(SourceRecipe::Tar {
             tar: _,
             blake3,
+            sha256,
+            sha512,
             patches,
             script,
+            extra,
         }) => {
-            let ident = blake3.clone().unwrap_or("no_tar_blake3_hash_info".into());
+            let ident = blake3
+                .clone()
+                .or_else(|| sha256.clone())
+                .or_else(|| sha512.clone())
+                .unwrap_or("no_tar_blake3_hash_info".into());
+            let ident = get_extra_source_ident(ident, extra);
             let patches_blake3 = get_patches_blake3(recipe_dir, patches, script)?;
             let cached = source_dir.is_dir();
             if !cached {
                 let source_tar = recipe_dir.join("source.tar");
-                let source_tar_blake3 = get_file_blake3(&source_tar)?;
+                if !source_tar.exists() {
+                    cache_restore(blake3, &source_tar)?;
+                }
                 if source_tar.exists() {
-                    if let Some(blake3) = blake3 {
-                        if source_tar_blake3 != *blake3 {
+                    if blake3.is_some() || sha256.is_some() || sha512.is_some() {
+                        if let Some((kind, sum)) =
+                            verify_tar_checksums(&source_tar, blake3, sha256, sha512)?
+                        {
                             bail_other_err!(
-                                "The downloaded tar blake3 {source_tar_blake3:?} is not equal to blake3 in recipe.toml"
+                                "The downloaded tar {kind} {sum:?} is not equal to {kind} in recipe.toml"
                             );
                         }
                         create_dir(&source_dir)?;
                         fetch_extract_tar(source_tar, &source_dir, logger)?;
+                        vendor_restore_extra_git(recipe, &source_dir, extra, logger)?;
+                        fetch_extra_sources(recipe_dir, &source_dir, extra, true, logger)?;
                         fetch_apply_patches(recipe_dir, patches, script, &source_dir, logger)?;
                     } else {
                         // need to trust this tar file
+                        let source_tar_blake3 = get_file_blake3(&source_tar)?;
                         bail_other_err!(
                             "Please add blake3 = {source_tar_blake3:?} to {recipe:?}",
                             recipe = recipe_dir.join("recipe.toml").display(),
@@ -134,6 +172,9 @@ pub fn fetch_offline(recipe: &CookRecipe, logger: &PtyOut) -> Result<FetchResult
                 }
             }
             offline_check_exists(&source_dir)?;
+            for e in extra {
+                offline_check_exists(&source_dir.join(&e.dir))?;
+            }
             FetchResult::new(source_dir, ident, patches_blake3, cached)
         }
     };
@@ -146,10 +187,10 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
     let source_dir = recipe_dir.join("source");
     let mut recipe = recipe;
     let mut recipe_owned;
-    if recipe.rule == "local" && !source_dir.exists() {
+    if split_rule(&recipe.rule).0 == "local" && !source_dir.exists() {
         // local source, but the source is missing
         recipe_owned = recipe.clone();
-        recipe_owned.rule = "source".into();
+        recipe_owned.rule = recipe.rule.replacen("local", "source", 1);
         recipe_owned.reload_recipe()?;
         recipe = &recipe_owned;
     }
@@ -201,12 +242,16 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
             git,
             upstream,
             branch,
+            tag,
             rev,
             patches,
             script,
             shallow_clone,
+            extra,
         }) => {
             //TODO: use libgit?
+            // rev is set from the lock file, which takes precedence over the tag
+            let rev = rev.as_ref().or(tag.as_ref());
             let shallow_clone =
                 shallow_clone.unwrap_or_else(|| crate::config::get_config().cook.git_treeless);
             let mut fetch_is_ran = false;
@@ -216,28 +261,46 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
                 let source_dir_tmp = recipe_dir.join("source.tmp");
                 create_dir_clean(&source_dir_tmp)?;
 
-                // Clone the repository to source.tmp
+                // Clone the repository to source.tmp, borrowing objects from the mirror.
+                // Treeless clones skip the mirror, as it would hold the full history.
+                let mirror = if shallow_clone {
+                    None
+                } else {
+                    git_mirror(git, logger)
+                };
                 let mut command = Command::new("git");
-                command
-                    .arg("clone")
-                    .arg("--recursive")
-                    .arg(translate_mirror(git));
-                if let Some(branch) = branch {
-                    command.arg("--branch").arg(branch);
+                command.arg("clone");
+                if let Some(mirror) = &mirror {
+                    // submodules are cloned below, each with its own mirror
+                    command.arg("--reference-if-able").arg(mirror);
+                } else {
+                    command.arg("--recursive");
+                    if shallow_clone {
+                        command
+                            .arg("--filter=tree:0")
+                            .arg("--also-filter-submodules");
+                    }
                 }
-                if shallow_clone {
-                    command
-                        .arg("--filter=tree:0")
-                        .arg("--also-filter-submodules");
+                command.arg(translate_mirror(git));
+                if let Some(branch) = branch.as_ref().or(tag.as_ref()) {
+                    command.arg("--branch").arg(branch);
                 }
                 command.arg(&source_dir_tmp);
-                if let Err(e) = run_command(command, logger) {
+                if mirror.is_some() {
+                    run_command(command, logger)?;
+                    manual_git_recursive_submodule(
+                        logger,
+                        &source_dir_tmp,
+                        vec!["update", "--init"],
+                    )?;
+                } else if let Err(e) = run_command(command, logger) {
                     if !is_redox() {
                         return Err(e);
                     }
                     // TODO: RedoxFS has a race condition problem with `--recursive` and running in multi CPU.
                     //       It is appear that running the submodule update separately fixes it. Remove this when
                     //       `git clone https://gitlab.redox-os.org/redox-os/relibc --recursive` proven to work in Redox OS.
+                    log_submodule_retry(logger, "update");
                     let mut cmds = vec!["update", "--init"];
                     if shallow_clone {
                         cmds.push("--filter=tree:0");
@@ -262,13 +325,20 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
                 let (head_rev, detached_rev) = get_git_head_rev(&source_dir)?;
                 match (cached_info, rev, detached_rev) {
                     (None, _, _) => false,
-                    (Some(s), _, _) if !s.is_updated(&head_rev, &patches_ident) => false,
+                    (Some(s), _, _)
+                        if !s.is_updated(
+                            &get_extra_source_ident(head_rev.clone(), extra),
+                            &patches_ident,
+                        ) =>
+                    {
+                        false
+                    }
                     (_, Some(rev), true) => get_git_tag_rev(&source_dir, rev, logger)
                         .is_ok_and(|exp_rev| exp_rev == head_rev),
                     (_, None, false) => match get_git_remote_tracking(&source_dir) {
                         Ok(remote) if !remote.check_updated(git, branch) => false,
                         Ok(remote) => {
-                            git_run_fetch(logger, &source_dir, git)?;
+                            git_run_fetch(logger, &source_dir, git, !shallow_clone)?;
                             fetch_is_ran = true;
                             match get_git_fetch_rev(
                                 &source_dir,
@@ -293,7 +363,7 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
 
             if !cached {
                 if !fetch_is_ran {
-                    git_run_fetch(logger, &source_dir, git)?;
+                    git_run_fetch(logger, &source_dir, git, !shallow_clone)?;
                 }
                 if let Some(_upstream) = upstream {
                     //TODO: set upstream URL (is this needed?)
@@ -343,79 +413,117 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
                     if !is_redox() {
                         return Err(e);
                     }
+                    log_submodule_retry(logger, "sync");
                     manual_git_recursive_submodule(logger, &source_dir, vec!["sync"])?;
                 }
 
                 // Update submodules
-                let mut command = Command::new("git");
-                command.arg("-C").arg(&source_dir);
-                command
-                    .arg("submodule")
-                    .arg("update")
-                    .arg("--init")
-                    .arg("--recursive");
-                if shallow_clone {
-                    command.arg("--filter=tree:0");
-                }
-                if let Err(e) = run_command(command, logger) {
-                    if !is_redox() {
-                        return Err(e);
-                    }
-                    let mut cmds = vec!["update", "--init"];
+                if git_cache_dir().is_some() && !shallow_clone {
+                    // each submodule borrows objects from its own mirror
+                    manual_git_recursive_submodule(logger, &source_dir, vec!["update", "--init"])?;
+                } else {
+                    let mut command = Command::new("git");
+                    command.arg("-C").arg(&source_dir);
+                    command
+                        .arg("submodule")
+                        .arg("update")
+                        .arg("--init")
+                        .arg("--recursive");
                     if shallow_clone {
-                        cmds.push("--filter=tree:0");
+                        command.arg("--filter=tree:0");
+                    }
+                    if let Err(e) = run_command(command, logger) {
+                        if !is_redox() {
+                            return Err(e);
+                        }
+                        log_submodule_retry(logger, "update");
+                        let mut cmds = vec!["update", "--init"];
+                        if shallow_clone {
+                            cmds.push("--filter=tree:0");
+                        }
+                        manual_git_recursive_submodule(logger, &source_dir, cmds)?;
                     }
-                    manual_git_recursive_submodule(logger, &source_dir, cmds)?;
                 }
 
+                fetch_extra_sources(recipe_dir, &source_dir, extra, false, logger)?;
                 fetch_apply_patches(recipe_dir, patches, script, &source_dir, logger)?;
             }
 
             let (head_rev, _) = get_git_head_rev(&source_dir)?;
-            FetchResult::new(source_dir, head_rev, patches_ident, cached)
+            let source_ident = get_extra_source_ident(head_rev, extra);
+            FetchResult::new(source_dir, source_ident, patches_ident, cached)
         }
         Some(SourceRecipe::Tar {
             tar,
             blake3,
+            sha256,
+            sha512,
             patches,
             script,
+            extra,
         }) => {
             let source_tar = recipe_dir.join("source.tar");
-            let source_ident = blake3.clone().unwrap_or("no_tar_blake3_hash_info".into());
+            let source_ident = blake3
+                .clone()
+                .or_else(|| sha256.clone())
+                .or_else(|| sha512.clone())
+                .unwrap_or("no_tar_blake3_hash_info".into());
+            let source_ident = get_extra_source_ident(source_ident, extra);
             let patches_ident = get_patches_blake3(recipe_dir, patches, script)?;
             let mut tar_updated = false;
             loop {
                 if !source_tar.is_file() {
                     tar_updated = true;
-                    download_wget(tar, &source_tar, logger)?;
+                    if !cache_restore(blake3, &source_tar)? {
+                        download(
+                            tar,
+                            &source_tar,
+                            Checksums {
+                                blake3,
+                                sha256,
+                                sha512,
+                            },
+                            logger,
+                        )?;
+                    }
                 }
                 if !check_source {
                     break;
                 }
-                let source_tar_blake3 = get_file_blake3(&source_tar)?;
-                if let Some(blake3) = blake3 {
-                    if source_tar_blake3 == *blake3 {
-                        break;
-                    }
+                if let Some((kind, sum)) =
+                    verify_tar_checksums(&source_tar, blake3, sha256, sha512)?
+                {
                     if tar_updated {
                         bail_other_err!(
-                            "The downloaded tar blake3 {source_tar_blake3:?} is not equal to blake3 in recipe.toml"
+                            "The downloaded tar {kind} {sum:?} is not equal to {kind} in recipe.toml"
                         )
                     } else {
                         log_to_pty!(
                             logger,
-                            "DEBUG: source tar blake3 is different and need redownload"
+                            "DEBUG: source tar {kind} is different and need redownload"
                         );
                         remove_all(&source_tar)?;
                     }
+                } else if blake3.is_none() {
+                    let source_tar_blake3 = get_file_blake3(&source_tar)?;
+                    if sha256.is_some() || sha512.is_some() {
+                        log_to_pty!(
+                            logger,
+                            "WARNING: set blake3 for {:?} to {:?}, or run \"repo capture-rev\"",
+                            source_tar.display(),
+                            source_tar_blake3
+                        );
+                    } else {
+                        log_to_pty!(
+                            logger,
+                            "WARNING: set blake3 for {:?} to {:?}",
+                            source_tar.display(),
+                            source_tar_blake3
+                        );
+                    }
+                    break;
                 } else {
-                    //TODO: set blake3 hash on the recipe with something like "cook fix"
-                    log_to_pty!(
-                        logger,
-                        "WARNING: set blake3 for {:?} to {:?}",
-                        source_tar.display(),
-                        source_tar_blake3
-                    );
+                    cache_store(blake3, &source_tar)?;
                     break;
                 }
             }
@@ -435,6 +543,7 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
                 let source_dir_tmp = recipe_dir.join("source.tmp");
                 create_dir_clean(&source_dir_tmp)?;
                 fetch_extract_tar(source_tar, &source_dir_tmp, logger)?;
+                fetch_extra_sources(recipe_dir, &source_dir_tmp, extra, false, logger)?;
                 fetch_apply_patches(recipe_dir, patches, script, &source_dir_tmp, logger)?;
 
                 // Move source.tmp to source atomically
@@ -457,16 +566,30 @@ pub fn fetch(recipe: &CookRecipe, check_source: bool, logger: &PtyOut) -> Result
         }
     };
 
-    if let BuildKind::Cargo { cargopath, .. } = &recipe.recipe.build.kind
+    if let BuildKind::Cargo { cargopath, .. } | BuildKind::CargoC { cargopath, .. } =
+        &recipe.recipe.build.kind
         && !result.cached
     {
         fetch_cargo(&result.source_dir, cargopath.as_ref(), logger)?;
     }
+    if let BuildKind::Go { .. } = &recipe.recipe.build.kind
+        && !result.cached
+    {
+        fetch_go(&result.source_dir, logger)?;
+    }
 
     result.apply_info(recipe)
 }
 
-fn git_run_fetch(logger: &PtyOut, source_dir: &PathBuf, git: &String) -> Result<()> {
+fn git_run_fetch(
+    logger: &PtyOut,
+    source_dir: &PathBuf,
+    git: &String,
+    use_mirror: bool,
+) -> Result<()> {
+    if use_mirror && let Some(mirror) = git_mirror(git, logger) {
+        git_add_alternate(source_dir, &mirror)?;
+    }
     let mut command = Command::new("git");
     command.arg("-C").arg(source_dir);
     command.arg("remote").arg("set-url").arg("origin").arg(git);
@@ -478,18 +601,25 @@ fn git_run_fetch(logger: &PtyOut, source_dir: &PathBuf, git: &String) -> Result<
     Ok(())
 }
 
-fn manual_git_recursive_submodule(
-    logger: &PtyOut,
-    source_dir: &PathBuf,
-    cmd: Vec<&str>,
-) -> Result<()> {
+fn log_submodule_retry(logger: &PtyOut, cmd: &str) {
     log_to_pty!(
         logger,
         "Git submodule {} failed, might be caused by race condition in RedoxFS, retrying without --recursive.",
-        cmd[0]
+        cmd
     );
+}
 
+/// Run a git submodule command in the source and each of its submodules, one level at a time.
+/// With the git cache, "update" clones each submodule with its mirror as a reference,
+/// unless it's treeless.
+fn manual_git_recursive_submodule(
+    logger: &PtyOut,
+    source_dir: &PathBuf,
+    cmd: Vec<&str>,
+) -> Result<()> {
     let mut repo_registry: BTreeMap<PathBuf, bool> = BTreeMap::new();
+    let use_mirrors =
+        cmd[0] == "update" && !cmd.contains(&"--filter=tree:0") && git_cache_dir().is_some();
 
     loop {
         let mut dirty_git = false;
@@ -530,7 +660,13 @@ fn manual_git_recursive_submodule(
         }
 
         for repo in pending_repos {
-            println!("==> Processing: {:?}", repo);
+            log_to_pty!(logger, "==> Processing: {:?}", repo);
+
+            if use_mirrors {
+                git_submodule_update_cached(logger, &source_dir.join(&repo), &cmd)?;
+                repo_registry.insert(repo, true);
+                continue;
+            }
 
             let mut command = Command::new("git");
             command.arg("-C").arg(&repo).current_dir(source_dir);
@@ -546,6 +682,60 @@ fn manual_git_recursive_submodule(
     }
 }
 
+/// Update submodules of a repo one by one, so each can use its mirror as a reference
+fn git_submodule_update_cached(logger: &PtyOut, repo_dir: &Path, cmd: &[&str]) -> Result<()> {
+    // resolve relative submodule URLs into the repo config
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo_dir);
+    command.arg("submodule").arg("init");
+    run_command(command, logger)?;
+
+    let git_config = |args: &[&str]| -> Result<String> {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(repo_dir).arg("config").args(args);
+        let output = command
+            .output()
+            .map_err(wrap_io_err!(repo_dir, "Running git config"))?;
+        // exit code 1 means nothing is found
+        if !output.status.success() && output.status.code() != Some(1) {
+            return Err(Error::Command(command, output.status));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    };
+    let urls = git_config(&["--get-regexp", r"^submodule\..*\.url$"])?;
+    for line in urls.lines() {
+        let Some((key, url)) = line.split_once(' ') else {
+            continue;
+        };
+        let Some(name) = key
+            .strip_prefix("submodule.")
+            .and_then(|k| k.strip_suffix(".url"))
+        else {
+            continue;
+        };
+        let path = git_config(&[
+            "-f",
+            ".gitmodules",
+            "--get",
+            &format!("submodule.{name}.path"),
+        ])?;
+        let path = path.trim();
+        if path.is_empty() {
+            continue;
+        }
+
+        let mut command = Command::new("git");
+        command.arg("-C").arg(repo_dir);
+        command.arg("submodule").args(cmd);
+        if let Some(mirror) = git_mirror(url, logger) {
+            command.arg("--reference").arg(mirror);
+        }
+        command.arg("--").arg(path);
+        run_command(command, logger)?;
+    }
+    Ok(())
+}
+
 fn get_patches_blake3(
     dir: &PathBuf,
     patches: &Vec<String>,
@@ -561,6 +751,159 @@ fn get_patches_blake3(
     )
 }
 
+/// Verify the tar file against every checksum specified in the recipe,
+/// returns the kind and actual sum of the first mismatching one
+pub fn verify_tar_checksums(
+    source_tar: &PathBuf,
+    blake3: &Option<String>,
+    sha256: &Option<String>,
+    sha512: &Option<String>,
+) -> Result<Option<(&'static str, String)>> {
+    if let Some(blake3) = blake3 {
+        let sum = get_file_blake3(source_tar)?;
+        if sum != *blake3 {
+            return Ok(Some(("blake3", sum)));
+        }
+    }
+    if let Some(sha256) = sha256 {
+        let sum = get_file_sha256(source_tar)?;
+        if !sum.eq_ignore_ascii_case(sha256) {
+            return Ok(Some(("sha256", sum)));
+        }
+    }
+    if let Some(sha512) = sha512 {
+        let sum = get_file_sha512(source_tar)?;
+        if !sum.eq_ignore_ascii_case(sha512) {
+            return Ok(Some(("sha512", sum)));
+        }
+    }
+    Ok(None)
+}
+
+/// Append identifiers of extra sources into the main source identifier
+fn get_extra_source_ident(source_ident: String, extra: &[ExtraSourceRecipe]) -> String {
+    extra
+        .iter()
+        .fold(source_ident, |ident, e| format!("{ident}+{}", e.ident()))
+}
+
+/// Fetch extra sources into subdirectories of `source_dir`
+fn fetch_extra_sources(
+    recipe_dir: &Path,
+    source_dir: &Path,
+    extra: &[ExtraSourceRecipe],
+    offline: bool,
+    logger: &PtyOut,
+) -> Result<()> {
+    for e in extra {
+        if Path::new(&e.dir)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            bail_other_err!(
+                "Extra source dir {:?} must be a relative path inside the source",
+                e.dir
+            );
+        }
+        let dir = source_dir.join(&e.dir);
+        match &e.source {
+            ExtraSource::Tar { tar, blake3 } => {
+                let extra_tar = recipe_dir.join(format!("source.{}.tar", e.dir.replace('/', "_")));
+                let mut tar_updated = false;
+                loop {
+                    if !extra_tar.is_file() && !cache_restore(blake3, &extra_tar)? {
+                        if offline {
+                            offline_check_exists(&extra_tar)?;
+                        }
+                        tar_updated = true;
+                        download(
+                            tar,
+                            &extra_tar,
+                            Checksums {
+                                blake3,
+                                ..Checksums::NONE
+                            },
+                            logger,
+                        )?;
+                    }
+                    let extra_tar_blake3 = get_file_blake3(&extra_tar)?;
+                    match blake3 {
+                        Some(blake3) if extra_tar_blake3 != *blake3 => {
+                            if tar_updated || offline {
+                                bail_other_err!(
+                                    "The downloaded tar blake3 {extra_tar_blake3:?} of extra source {:?} is not equal to blake3 in recipe.toml",
+                                    e.dir
+                                )
+                            }
+                            remove_all(&extra_tar)?;
+                        }
+                        Some(_) => {
+                            cache_store(blake3, &extra_tar)?;
+                            break;
+                        }
+                        None => {
+                            log_to_pty!(
+                                logger,
+                                "WARNING: set blake3 for extra source {:?} to {:?}",
+                                e.dir,
+                                extra_tar_blake3
+                            );
+                            break;
+                        }
+                    }
+                }
+                if dir.exists() {
+                    remove_all(&dir)?;
+                }
+                create_dir(&dir)?;
+                fetch_extract_tar(extra_tar, &dir, logger)?;
+            }
+            ExtraSource::Git { git, branch, rev } => {
+                if !dir.join(".git").exists() {
+                    if offline {
+                        offline_check_exists(&dir.join(".git"))?;
+                    }
+                    if dir.exists() {
+                        remove_all(&dir)?;
+                    }
+                    let mut command = Command::new("git");
+                    command
+                        .arg("clone")
+                        .arg("--recursive")
+                        .arg(translate_mirror(git));
+                    if let Some(branch) = branch {
+                        command.arg("--branch").arg(branch);
+                    }
+                    command.arg(&dir);
+                    run_command(command, logger)?;
+                }
+                if let Some(rev) = rev {
+                    let (head_rev, _) = get_git_head_rev(&dir)?;
+                    if get_git_tag_rev(&dir, rev, logger).ok() != Some(head_rev) {
+                        if !offline {
+                            git_run_fetch(logger, &dir, git, true)?;
+                        }
+                        let mut command = Command::new("git");
+                        command.arg("-C").arg(&dir);
+                        command.arg("checkout").arg(rev);
+                        run_command(command, logger)?;
+
+                        let mut command = Command::new("git");
+                        command.arg("-C").arg(&dir);
+                        command
+                            .arg("submodule")
+                            .arg("update")
+                            .arg("--init")
+                            .arg("--recursive");
+                        run_command(command, logger)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn fetch_make_symlink(source_dir: &PathBuf, same_as: &String) -> Result<()> {
     let target_dir = Path::new(same_as).join("source");
     if !source_dir.is_symlink() {
@@ -605,7 +948,7 @@ pub(crate) fn fetch_resolve_canon(
     let mut recipe = CookRecipe::from_path(canon_dir.as_path(), true, source_recipe.name.is_host())
         .map_err(Error::from)?;
     if !source_recipe.rule.is_empty() {
-        recipe.apply_filesystem_config(&source_recipe.rule)?;
+        recipe.apply_filesystem_config(split_rule(&source_recipe.rule).0)?;
     }
     // Copying from repo.rs, not ideal, but works
     if let Some(gitrev) = crate::config::get_config()
@@ -625,6 +968,12 @@ pub(crate) fn fetch_extract_tar(
     source_dir_tmp: &PathBuf,
     logger: &PtyOut,
 ) -> Result<()> {
+    match ArchiveFormat::detect(&source_tar)? {
+        ArchiveFormat::Zip => return extract_zip(&source_tar, source_dir_tmp, logger),
+        ArchiveFormat::SevenZip => return extract_7z(&source_tar, source_dir_tmp, logger),
+        ArchiveFormat::Tar => {}
+    }
+
     let mut command = Command::new("tar");
     let verbose = crate::config::get_config().cook.verbose;
     if is_redox() {
@@ -649,29 +998,47 @@ pub(crate) fn fetch_cargo(
     cargopath: Option<&String>,
     logger: &PtyOut,
 ) -> Result<()> {
-    if !check_cargo_available() {
+    let Some(mut command) = cargo_command() else {
         return Ok(());
-    }
-
+    };
     let mut source_dir = source_dir.clone();
     if let Some(cargopath) = cargopath {
         source_dir = source_dir.join(cargopath);
     }
+    command.arg("fetch");
+    command.arg("--manifest-path");
+    command.arg(source_dir.join("Cargo.toml").into_os_string());
+    run_command(command, logger)?;
+    Ok(())
+}
 
+/// Vendor the Go modules of the source, so it can be built without downloading them
+pub(crate) fn fetch_go(source_dir: &Path, logger: &PtyOut) -> Result<()> {
+    if !source_dir.join("go.mod").is_file() {
+        return Ok(());
+    }
+    let mut command = Command::new("go");
+    command.current_dir(source_dir);
+    command.env("GOTOOLCHAIN", "local");
+    command.arg("mod").arg("vendor");
+    run_command(command, logger)?;
+    Ok(())
+}
+
+/// The cargo command to fetch crates with, or None if cargo is not available
+pub(crate) fn cargo_command() -> Option<Command> {
+    if !check_cargo_available() {
+        return None;
+    }
     let local_redoxer = Path::new("target/release/cookbook_redoxer");
-    let mut command = if is_redox() && !local_redoxer.is_file() {
+    Some(if is_redox() && !local_redoxer.is_file() {
         Command::new("cookbook_redoxer")
     } else {
         let cookbook_redoxer = local_redoxer
             .canonicalize()
             .unwrap_or(PathBuf::from("cargo"));
         Command::new(&cookbook_redoxer)
-    };
-    command.arg("fetch");
-    command.arg("--manifest-path");
-    command.arg(source_dir.join("Cargo.toml").into_os_string());
-    run_command(command, logger)?;
-    Ok(())
+    })
 }
 
 /// Check if "$REDOXER_TOOLCHAIN/bin/cargo" is available.
@@ -759,6 +1126,8 @@ pub fn fetch_remote(
 
             // manager.download(file, 0, dest)
         } else {
+            vendor_restore_package(recipe, &source_pkgar)?;
+            vendor_restore_package(recipe, &source_toml)?;
             offline_check_exists(&source_pkgar)?;
             offline_check_exists(&source_toml)?;
         }
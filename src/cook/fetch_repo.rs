@@ -6,7 +6,7 @@ use std::{
     time::Duration,
 };
 
-use crate::cook::fs;
+use crate::cook::{fs, vendor};
 use pkg::{
     PackageName, RemotePackage, RepoManager, Repository,
     callback::{Callback, PlainCallback, SilentCallback},
@@ -36,6 +36,14 @@ fn load_cached_repo(path: &Path) -> Option<Repository> {
 }
 
 fn init_binary_repo() -> (RepoManager, Repository) {
+    let repo_path = PathBuf::from("build/remotes");
+    if crate::config::get_config().cook.offline
+        && !repo_path.is_dir()
+        && let Err(e) = vendor::vendor_restore_remotes(&repo_path)
+    {
+        eprintln!("Unable to copy server repo.toml from the vendor bundle: {e}");
+    }
+
     let callback = Rc::new(RefCell::new(SilentCallback::new()));
     let download_backend = CurlBackend::new().expect("Curl not found");
     let mut repo = RepoManager::new(callback, Box::new(download_backend));
@@ -44,7 +52,6 @@ fn init_binary_repo() -> (RepoManager, Repository) {
     repo.add_remote(&remote_source, target)
         .expect("Unable to add remote");
 
-    let repo_path = PathBuf::from("build/remotes");
     repo.set_download_path(repo_path.clone());
     repo.sync_keys().expect("Unable to sync keys");
 
@@ -12,7 +12,6 @@ use walkdir::{DirEntry, WalkDir};
 
 use crate::{
     Error, Result, bail_other_err,
-    config::translate_mirror,
     cook::pty::{PtyOut, spawn_to_pipe},
     wrap_io_err, wrap_other_err,
 };
@@ -278,18 +277,6 @@ pub fn offline_check_exists(path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn download_wget(url: &str, dest: &PathBuf, logger: &PtyOut) -> Result<()> {
-    if !dest.is_file() {
-        let dest_tmp = PathBuf::from(format!("{}.tmp", dest.display()));
-        let mut command = Command::new("wget");
-        command.arg(translate_mirror(url));
-        command.arg("--continue").arg("-O").arg(&dest_tmp);
-        run_command(command, logger)?;
-        rename(&dest_tmp, dest)?;
-    }
-    Ok(())
-}
-
 pub fn read_to_string(path: &Path) -> Result<String> {
     fs::read_to_string(path).map_err(wrap_io_err!(path, "Reading file"))
 }
@@ -298,6 +285,25 @@ pub fn get_file_blake3(path: &PathBuf) -> Result<String> {
     get_blake3(path).map(|s| s.to_hex().to_string())
 }
 
+pub fn get_file_sha256(path: &PathBuf) -> Result<String> {
+    get_sha2::<sha2::Sha256>(path)
+}
+
+pub fn get_file_sha512(path: &PathBuf) -> Result<String> {
+    get_sha2::<sha2::Sha512>(path)
+}
+
+fn get_sha2<D: sha2::Digest + Write>(path: &PathBuf) -> Result<String> {
+    let mut f = fs::File::open(path).map_err(wrap_io_err!(path, "Opening file for sha2"))?;
+    let mut hasher = D::new();
+    io::copy(&mut f, &mut hasher).map_err(wrap_io_err!(path, "Reading file for sha2"))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
 fn get_blake3(path: &PathBuf) -> Result<Hash> {
     let mut f = fs::File::open(path).map_err(wrap_io_err!(path, "Opening file for blake3"))?;
     let hash = blake3::Hasher::new()
@@ -630,3 +636,14 @@ pub fn get_git_rev_before_date(dir: &PathBuf, date: &str) -> Result<String> {
     }
     Ok(rev)
 }
+
+/// Run git in a dir, returning its trimmed stdout
+pub fn git_output(dir: &Path, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args);
+    let output = command.output().map_err(wrap_io_err!(dir, "Running git"))?;
+    if !output.status.success() {
+        return Err(Error::Command(command, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use crate::{
+    Error, Result,
+    config::{translate_mirror, try_get_config},
+    cook::{
+        fs::{create_dir, remove_all, rename, run_command},
+        pty::PtyOut,
+    },
+    log_to_pty, wrap_io_err,
+};
+
+// This file contains the cache of bare git mirrors. Sources are cloned with the mirrors as
+// alternates, so repositories used by many recipes are only downloaded and stored once.
+// Clones keep reading objects from the mirrors, so mirrors never prune anything and the
+// cache dir must not be deleted while any source cloned with it still exists.
+
+/// Mirrors already updated by this process
+static UPDATED_MIRRORS: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// The configured cache dir, or None if the cache is disabled
+pub fn git_cache_dir() -> Option<PathBuf> {
+    let dir = &try_get_config()?.cook.git_cache;
+    if dir.as_os_str().is_empty() {
+        None
+    } else {
+        Some(dir.clone())
+    }
+}
+
+/// Create or update the bare mirror of `url`, returns its absolute path.
+/// Failures are only logged, as the source can still be cloned without the mirror.
+pub fn git_mirror(url: &str, logger: &PtyOut) -> Option<PathBuf> {
+    let cache_dir = git_cache_dir()?;
+    let url = translate_mirror(url);
+    match update_mirror(&cache_dir, &url, logger) {
+        Ok(mirror) => Some(mirror),
+        Err(e) => {
+            log_to_pty!(logger, "WARNING: unable to update git mirror of {url}: {e}");
+            None
+        }
+    }
+}
+
+fn update_mirror(cache_dir: &Path, url: &str, logger: &PtyOut) -> Result<PathBuf> {
+    create_dir(cache_dir)?;
+    let cache_dir = cache_dir
+        .canonicalize()
+        .map_err(wrap_io_err!(cache_dir, "Canonicalizing git cache dir"))?;
+    let mirror = cache_dir.join(mirror_name(url));
+    if UPDATED_MIRRORS
+        .lock()
+        .unwrap()
+        .get_or_insert_default()
+        .contains(&mirror)
+    {
+        return Ok(mirror);
+    }
+
+    // mirrors are shared with parallel fetches and other checkouts
+    let lock_path = mirror.with_extension("lock");
+    let lock = File::create(&lock_path).map_err(wrap_io_err!(lock_path, "Creating lock"))?;
+    if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(Error::from_io_error(
+            std::io::Error::last_os_error(),
+            "Locking git mirror",
+        ));
+    }
+
+    if mirror.is_dir() {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&mirror);
+        // refs deleted upstream are kept, clones may still need their objects
+        command.arg("fetch").arg("origin");
+        run_command(command, logger)?;
+    } else {
+        let mirror_tmp = mirror.with_extension("tmp");
+        if mirror_tmp.exists() {
+            remove_all(&mirror_tmp)?;
+        }
+        let mut command = Command::new("git");
+        command
+            .arg("clone")
+            .arg("--mirror")
+            .arg(url)
+            .arg(&mirror_tmp);
+        run_command(command, logger)?;
+        rename(&mirror_tmp, &mirror)?;
+    }
+
+    // objects made unreachable by force pushes may still be borrowed by clones,
+    // this is also set on mirrors created before it was introduced
+    let mut command = Command::new("git");
+    command.arg("-C").arg(&mirror);
+    command.arg("config").arg("gc.pruneExpire").arg("never");
+    run_command(command, logger)?;
+
+    UPDATED_MIRRORS
+        .lock()
+        .unwrap()
+        .get_or_insert_default()
+        .insert(mirror.clone());
+    Ok(mirror)
+}
+
+/// Let an existing clone borrow objects from a mirror, so fetching it downloads less
+pub fn git_add_alternate(repo_dir: &Path, mirror: &Path) -> Result<()> {
+    let info_dir = repo_dir.join(".git/objects/info");
+    if !info_dir.parent().is_some_and(|p| p.is_dir()) {
+        // not a plain git dir, such as a submodule
+        return Ok(());
+    }
+    let alternates = info_dir.join("alternates");
+    let objects = mirror.join("objects");
+    let objects = objects.to_string_lossy();
+    let existing = fs::read_to_string(&alternates).unwrap_or_default();
+    if existing.lines().any(|l| l == objects) {
+        return Ok(());
+    }
+    create_dir(&info_dir)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&alternates)
+        .map_err(wrap_io_err!(alternates, "Opening git alternates"))?;
+    writeln!(file, "{objects}").map_err(wrap_io_err!(alternates, "Writing git alternates"))
+}
+
+/// A readable dir name for the mirror, ignoring the URL scheme and ".git" suffix,
+/// followed by a hash of the full URL as different URLs may have the same readable name
+pub(crate) fn mirror_name(full_url: &str) -> String {
+    let url = full_url
+        .split_once("://")
+        .map_or(full_url, |(_, rest)| rest);
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let name: String = url
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = blake3::hash(full_url.as_bytes()).to_hex();
+    format!("{name}-{}.git", &hash[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cook::git_cache::mirror_name;
+
+    #[test]
+    fn git_mirror_name() {
+        let relibc = mirror_name("https://gitlab.redox-os.org/redox-os/relibc.git");
+        assert!(relibc.starts_with("gitlab.redox-os.org_redox-os_relibc-"));
+        assert!(relibc.ends_with(".git"));
+        assert!(
+            mirror_name("git@github.com:rust-lang/rust.git")
+                .starts_with("git_github.com_rust-lang_rust-")
+        );
+        // same readable name, but different repositories
+        assert_ne!(
+            mirror_name("https://example.com/a/b_c"),
+            mirror_name("https://example.com/a_b/c")
+        );
+        assert_eq!(
+            mirror_name("https://example.com/a/b_c"),
+            mirror_name("https://example.com/a/b_c")
+        );
+    }
+}
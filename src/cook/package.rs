@@ -4,17 +4,17 @@ use std::{
 };
 
 use pkg::{InstallState, Package, PackageName, PackagePrefix, PackageState};
-use pkgar::{PackageFile, Transaction, ext::PackageSrcExt};
+use pkgar::{PackageFile, PackageHead, Transaction, ext::PackageSrcExt};
 use pkgar_core::HeaderFlags;
 use pkgar_keys::PublicKeyFile;
 
 use crate::{
-    Error, Result,
+    Error, Result, bail_other_err,
     config::CookConfig,
     cook::{cook_build::BuildResult, fetch, fs::*, pty::PtyOut},
     log_to_pty,
     recipe::{BuildKind, CookRecipe, OptionalPackageRecipe},
-    wrap_io_err,
+    staged_pkg, wrap_io_err,
 };
 
 pub fn package(
@@ -169,13 +169,22 @@ pub fn package_toml(
 
     let ident_source = fetch::fetch_get_source_info(recipe)?;
 
+    let version = match package_suffix.and_then(|p| p.version.clone()) {
+        Some(version) => version,
+        None => recipe.guess_version().unwrap_or("TODO".into()),
+    };
+    let description = match package_suffix {
+        Some(p) => p.description.as_ref(),
+        None => recipe.recipe.package.description.as_ref(),
+    };
+
     let package = Package {
         name: PackageName::new(get_package_name(
             recipe.name.without_prefix(),
             package_suffix,
         ))
         .unwrap(),
-        version: recipe.guess_version().unwrap_or("TODO".into()),
+        version,
         target: recipe.target.to_string(),
         blake3: hash,
         network_size,
@@ -187,6 +196,47 @@ pub fn package_toml(
         ..Default::default()
     };
 
+    // metadata that pkg::Package does not know about, kept for the web index
+    let mut package = toml::Table::try_from(&package)
+        .map_err(|e| Error::Other(format!("Serializing package: {e}")))?;
+    if let Some(description) = description {
+        package
+            .entry("description")
+            .or_insert(description.as_str().into());
+    }
+    let meta = &recipe.recipe.package;
+    for (key, value) in [
+        ("license", &meta.license),
+        ("homepage", &meta.homepage),
+        ("upstream_url", &meta.upstream_url),
+    ] {
+        if let Some(value) = value {
+            package.entry(key).or_insert(value.as_str().into());
+        }
+    }
+    if !meta.maintainers.is_empty() {
+        package
+            .entry("maintainers")
+            .or_insert(meta.maintainers.clone().into());
+    }
+    if !recipe.recipe.variants.is_empty() {
+        package
+            .entry("variants")
+            .or_insert(recipe.recipe.enabled_variants.clone().into());
+    }
+    if package_suffix.is_none() {
+        for (key, names) in [
+            ("provides", &meta.provides),
+            ("conflicts", &meta.conflicts),
+            ("replaces", &meta.replaces),
+        ] {
+            if !names.is_empty() {
+                let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+                package.entry(key).or_insert(names.into());
+            }
+        }
+    }
+
     serialize_and_write(&toml_path, &package)?;
     Ok(())
 }
@@ -243,6 +293,14 @@ fn get_package_name_inner(name: &str, package: Option<&str>) -> String {
     prefix_name
 }
 
+/// Package names listed under a relation of a package toml, such as "conflicts"
+fn package_relations<'a>(meta: &'a toml::Table, key: &str) -> Vec<&'a str> {
+    meta.get(key)
+        .and_then(|names| names.as_array())
+        .map(|names| names.iter().filter_map(|n| n.as_str()).collect())
+        .unwrap_or_default()
+}
+
 pub fn package_handle_push(
     state: Option<&mut PackageState>,
     archive_path: &Path,
@@ -262,6 +320,31 @@ pub fn package_handle_push(
         }
         return Ok(false);
     };
+    let pkg_meta: toml::Table = read_toml(&archive_toml)?;
+    let name = pkg_toml.name.as_str();
+    let conflicts = package_relations(&pkg_meta, "conflicts");
+    let replaces = package_relations(&pkg_meta, "replaces");
+    for installed in state.installed.keys() {
+        let installed = installed.as_str();
+        if installed == name || replaces.contains(&installed) {
+            continue;
+        }
+        // the installed package may also declare a conflict with this one
+        let conflicts_back = || {
+            let installed = PackageName::new(installed).ok()?;
+            let (installed, dir) = staged_pkg::resolve(&installed).ok()?;
+            let meta = read_toml(&staged_pkg::stage_file(dir, installed.suffix())).ok()?;
+            Some(package_relations(&meta, "conflicts").contains(&name))
+        };
+        if conflicts.contains(&installed) || conflicts_back() == Some(true) {
+            bail_other_err!(
+                "Package {} conflicts with installed package {}",
+                name,
+                installed
+            );
+        }
+    }
+
     let (cached, pstate) = match state.installed.get(&pkg_toml.name) {
         Some(s) if pkg_toml.blake3 == s.blake3 => (true, None),
         Some(s) => (false, Some((s.manual, s.dependents.clone()))),
@@ -272,6 +355,21 @@ pub fn package_handle_push(
     };
 
     if let Some((manual, dependents)) = pstate {
+        // old names of this package are uninstalled first, as their files may be taken over
+        for old in replaces {
+            let Some(old) = state.installed.keys().find(|n| n.as_str() == old).cloned() else {
+                continue;
+            };
+            let head_path =
+                sysroot_dir.join(format!("var/lib/packages/{}.pkgar_head", old.as_str()));
+            if head_path.is_file() {
+                let pkey = PublicKeyFile::open(pkey_path)?.pkey;
+                let mut head = PackageHead::new(&head_path, sysroot_dir, &pkey)?;
+                Transaction::remove(&mut head, sysroot_dir)?.commit()?;
+                remove_all(&head_path)?;
+            }
+            state.installed.remove(&old);
+        }
         if archive_path.is_file() {
             let pkey = PublicKeyFile::open(pkey_path)?.pkey;
             let mut package = PackageFile::new(archive_path, &pkey)?;
@@ -35,7 +35,7 @@ function DYNAMIC_INIT {
 
     # TODO: check paths for spaces
     export LDFLAGS="${USER_LDFLAGS}-Wl,-rpath-link,${COOKBOOK_SYSROOT}/lib -L${COOKBOOK_SYSROOT}/lib -Wl,--export-dynamic"
-    export RUSTFLAGS="-C target-feature=-crt-static -L native=${COOKBOOK_SYSROOT}/lib -C link-arg=-Wl,-rpath-link,${COOKBOOK_SYSROOT}/lib"
+    export RUSTFLAGS="${USER_RUSTFLAGS}-C target-feature=-crt-static -L native=${COOKBOOK_SYSROOT}/lib -C link-arg=-Wl,-rpath-link,${COOKBOOK_SYSROOT}/lib"
     export COOKBOOK_DYNAMIC=1
 
     if [ function = $(type -t reexport_flags) ]; then
@@ -106,6 +106,9 @@ export CPPFLAGS="${CPPFLAGS:+$CPPFLAGS }-I${COOKBOOK_SYSROOT}/include"
 USER_LDFLAGS="${LDFLAGS:+$LDFLAGS }"
 export LDFLAGS="${USER_LDFLAGS}-L${COOKBOOK_SYSROOT}/lib --static"
 
+# RUSTFLAGS from the recipe, which DYNAMIC_INIT puts in front of its own
+USER_RUSTFLAGS=
+
 # This reexport C variables into custom build script that can be consumed by cc crate
 function reexport_flags {
     target=${TARGET//-/_}
@@ -143,6 +146,13 @@ build_flags+=" --offline"
 install_flags+=" --offline"
 fi
 
+# crates from the bundle made by "repo vendor"
+if [ ! -z "${COOKBOOK_CARGO_VENDOR}" ]
+then
+export CARGO_SOURCE_CRATES_IO_REPLACE_WITH="vendored-sources"
+export CARGO_SOURCE_VENDORED_SOURCES_DIRECTORY="${COOKBOOK_CARGO_VENDOR}"
+fi
+
 reexport_flags
 
 COOKBOOK_CARGO="${COOKBOOK_REDOXER}"
@@ -204,6 +214,30 @@ function cookbook_cargo_packages_prefixed {
     done
 }
 
+# cargo-c template, for Rust crates that provide C library
+COOKBOOK_CARGO_C="cargo"
+COOKBOOK_CARGO_C_LIBRARY_TYPE="cdylib staticlib"
+function cookbook_cargo_c {
+    library_flags=()
+    if [ "${COOKBOOK_DYNAMIC}" == "1" ]
+    then
+        for library_type in ${COOKBOOK_CARGO_C_LIBRARY_TYPE}
+        do
+            library_flags+=(--library-type "${library_type}")
+        done
+    else
+        library_flags+=(--library-type staticlib)
+    fi
+    "${COOKBOOK_CARGO_C}" cinstall \
+        --manifest-path "${COOKBOOK_SOURCE}${COOKBOOK_CARGO_PATH:+/$COOKBOOK_CARGO_PATH}/Cargo.toml" \
+        --target "${TARGET}" \
+        --destdir "${COOKBOOK_STAGE}" \
+        --prefix /usr \
+        --libdir lib \
+        "${library_flags[@]}" \
+        ${build_flags} -j "${COOKBOOK_MAKE_JOBS}" ${COOKBOOK_CARGO_FLAGS[@]} "$@"
+}
+
 # configure template
 COOKBOOK_CONFIGURE="${COOKBOOK_SOURCE}/configure"
 COOKBOOK_CONFIGURE_FLAGS=(
@@ -221,6 +255,19 @@ function cookbook_configure {
     "${COOKBOOK_MAKE}" install DESTDIR="${COOKBOOK_STAGE}"
 }
 
+# make template, for projects with plain Makefile
+COOKBOOK_MAKE_INSTALL_TARGET="install"
+function cookbook_make {
+    # plain Makefile rarely support out-of-tree build
+    rsync -a "${COOKBOOK_SOURCE}/" ./source
+    make_dir="./source${COOKBOOK_MAKE_PATH:+/$COOKBOOK_MAKE_PATH}"
+    "${COOKBOOK_MAKE}" -C "${make_dir}" -j "${COOKBOOK_MAKE_JOBS}" \
+        PREFIX=/usr prefix=/usr "${COOKBOOK_MAKE_FLAGS[@]}" "$@"
+    "${COOKBOOK_MAKE}" -C "${make_dir}" \
+        PREFIX=/usr prefix=/usr "${COOKBOOK_MAKE_FLAGS[@]}" "$@" \
+        "${COOKBOOK_MAKE_INSTALL_TARGET}" DESTDIR="${COOKBOOK_STAGE}"
+}
+
 COOKBOOK_CMAKE="cmake"
 COOKBOOK_NINJA="ninja"
 COOKBOOK_CMAKE_FLAGS=(
@@ -372,6 +419,56 @@ function cookbook_meson {
     "${COOKBOOK_NINJA}" -j"${COOKBOOK_MAKE_JOBS}"
     DESTDIR="${COOKBOOK_STAGE}" "${COOKBOOK_NINJA}" install -j"${COOKBOOK_MAKE_JOBS}"
 }
+COOKBOOK_GO="go"
+COOKBOOK_GO_FLAGS=(
+    -trimpath
+)
+COOKBOOK_GO_LDFLAGS=(
+    -s
+    -w
+)
+function GO_INIT {
+    # don't let go download other toolchain
+    export GOTOOLCHAIN=local
+    # cgo is only available for the host, redox has no go toolchain to link with
+    case "${TARGET}" in
+        x86_64-unknown-linux-gnu)  export GOARCH=amd64 GOOS=linux CGO_ENABLED=1;;
+        aarch64-unknown-linux-gnu) export GOARCH=arm64 GOOS=linux CGO_ENABLED=1;;
+        i586-unknown-redox)     export GOARCH=386 GOOS=redox CGO_ENABLED=0;;
+        x86_64-unknown-redox)  export GOARCH=amd64 GOOS=redox CGO_ENABLED=0;;
+        aarch64-unknown-redox) export GOARCH=arm64 GOOS=redox CGO_ENABLED=0;;
+        riscv64gc-unknown-redox) export GOARCH=riscv64 GOOS=redox CGO_ENABLED=0;;
+    esac
+
+    # keep build cache in the build directory
+    export GOPATH="${COOKBOOK_BUILD}/go"
+    export GOCACHE="${GOPATH}/cache"
+
+    # CC and friends are already set for the target
+    export CGO_CFLAGS="${CFLAGS:+$CFLAGS }${CPPFLAGS}"
+    export CGO_CXXFLAGS="${CXXFLAGS:+$CXXFLAGS }${CPPFLAGS}"
+    export CGO_LDFLAGS="${LDFLAGS}"
+
+    # modules are vendored into the source when it is fetched
+    if [ -f "${COOKBOOK_SOURCE}/vendor/modules.txt" ]
+    then
+        export GOFLAGS="${GOFLAGS:+$GOFLAGS }-mod=vendor"
+    fi
+    if [ ! -z "${COOKBOOK_OFFLINE}" ]
+    then
+        export GOPROXY=off
+    fi
+}
+function cookbook_go {
+    GO_INIT
+    mkdir -pv "${COOKBOOK_STAGE}/usr/bin"
+    "${COOKBOOK_GO}" build -C "${COOKBOOK_SOURCE}" \
+        -o "${COOKBOOK_STAGE}/usr/bin/" \
+        -ldflags "${COOKBOOK_GO_LDFLAGS[*]}" \
+        "${COOKBOOK_GO_FLAGS[@]}" \
+        "${@:-.}"
+}
+
 COOKBOOK_PYTHON="${COOKBOOK_TOOLCHAIN}/bin/python3"
 COOKBOOK_PYTHON_V="python3.12"
 function PYTHON_INIT {
@@ -0,0 +1,561 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, Result, bail_other_err,
+    config::try_get_config,
+    cook::{
+        cache::store,
+        fetch::{cargo_command, fetch_resolve_canon},
+        fs::{
+            copy_dir_all, create_dir, git_output, read_toml, remove_all, run_command,
+            serialize_and_write,
+        },
+        git_cache::mirror_name,
+        package::package_source_paths,
+        pty::PtyOut,
+    },
+    recipe::{BuildKind, CookRecipe, ExtraSource, ExtraSourceRecipe, SourceRecipe},
+    wrap_io_err,
+};
+
+// This file contains the offline bundle made by `repo vendor`, which has everything needed
+// to fetch a set of recipes on a host without internet access:
+//
+//   manifest.toml              what is vendored for each recipe
+//   sources/<blake3>           source and extra tarballs, like the source cache
+//   git/<mirror>               bare repos with the checked out commits of git sources
+//   cargo/                     crates.io dependencies of cargo recipes, from "cargo vendor"
+//   packages/<target>/<name>/  binary packages of remote recipes
+//   remotes/                   the binary repo metadata, copied into build/remotes
+//
+// Cargo git dependencies are not covered, as they need a source replacement each.
+
+const MANIFEST_FILE: &str = "manifest.toml";
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VendorManifest {
+    pub recipes: BTreeMap<String, VendoredRecipe>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VendoredRecipe {
+    /// blake3 of the source tarball and extra tarballs
+    pub tars: Vec<String>,
+    pub git: Option<VendoredGit>,
+    /// extra git sources, by their dir
+    pub extra_git: BTreeMap<String, VendoredGit>,
+    /// whether crates are vendored for this recipe
+    pub cargo: bool,
+    /// file names of binary packages
+    pub packages: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VendoredGit {
+    pub rev: String,
+    /// bare repo name in "git"
+    pub mirror: String,
+    /// bare repo names of submodules, by their path from the checkout
+    pub submodules: BTreeMap<String, String>,
+}
+
+impl VendorManifest {
+    pub fn load(bundle_dir: &Path) -> Result<Self> {
+        let manifest = bundle_dir.join(MANIFEST_FILE);
+        if manifest.is_file() {
+            read_toml(&manifest)
+        } else {
+            Ok(Self::default())
+        }
+    }
+}
+
+/// The configured bundle dir, or None if fetching from a bundle is disabled
+pub fn vendor_dir() -> Option<PathBuf> {
+    let dir = &try_get_config()?.cook.vendor;
+    if dir.as_os_str().is_empty() {
+        None
+    } else {
+        Some(dir.clone())
+    }
+}
+
+/// The vendored crates dir, if the bundle has one
+pub fn vendor_cargo_dir() -> Option<PathBuf> {
+    vendor_dir()?.join("cargo").canonicalize().ok()
+}
+
+/// A bundle being written by `repo vendor`
+pub struct VendorBundle {
+    dir: PathBuf,
+    manifest: VendorManifest,
+}
+
+impl VendorBundle {
+    /// Open a new or existing bundle, recipes vendored previously are kept
+    pub fn open(dir: &Path) -> Result<Self> {
+        create_dir(dir)?;
+        let dir = dir
+            .canonicalize()
+            .map_err(wrap_io_err!(dir, "Canonicalizing bundle dir"))?;
+        let manifest = VendorManifest::load(&dir)?;
+        Ok(Self { dir, manifest })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copy the sources of a fetched recipe into the bundle
+    pub fn add_recipe(&mut self, recipe: &CookRecipe, logger: &PtyOut) -> Result<()> {
+        let recipe_dir = &recipe.dir;
+        let source_dir = recipe_dir.join("source");
+        let mut vendored = VendoredRecipe::default();
+        match &recipe.recipe.build.kind {
+            BuildKind::None => return Ok(()),
+            BuildKind::Remote => {
+                self.add_packages(recipe, &mut vendored)?;
+                self.manifest
+                    .recipes
+                    .insert(recipe.name.as_str().to_string(), vendored);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let extra: &[ExtraSourceRecipe] = match &recipe.recipe.source {
+            Some(SourceRecipe::SameAs { same_as }) => {
+                let canon = fetch_resolve_canon(same_as, recipe)?;
+                self.add_recipe(&canon, logger)?;
+                &[]
+            }
+            Some(SourceRecipe::Git { git, extra, .. }) => {
+                vendored.git = Some(self.add_git(&source_dir, git, logger)?);
+                extra.as_slice()
+            }
+            Some(SourceRecipe::Tar { blake3, extra, .. }) => {
+                let blake3 = self.add_tar(&recipe_dir.join("source.tar"), blake3)?;
+                vendored.tars.push(blake3);
+                extra.as_slice()
+            }
+            // local sources are expected to be there
+            Some(SourceRecipe::Path { .. }) | None => &[],
+        };
+        for e in extra {
+            match &e.source {
+                ExtraSource::Tar { blake3, .. } => {
+                    let extra_tar =
+                        recipe_dir.join(format!("source.{}.tar", e.dir.replace('/', "_")));
+                    let blake3 = self.add_tar(&extra_tar, blake3)?;
+                    vendored.tars.push(blake3);
+                }
+                ExtraSource::Git { git, .. } => {
+                    let vendored_git = self.add_git(&source_dir.join(&e.dir), git, logger)?;
+                    vendored.extra_git.insert(e.dir.clone(), vendored_git);
+                }
+            }
+        }
+
+        if let BuildKind::Cargo { cargopath, .. } | BuildKind::CargoC { cargopath, .. } =
+            &recipe.recipe.build.kind
+        {
+            self.add_cargo(&source_dir, cargopath.as_ref(), logger)?;
+            vendored.cargo = true;
+        }
+
+        self.manifest
+            .recipes
+            .insert(recipe.name.as_str().to_string(), vendored);
+        Ok(())
+    }
+
+    fn add_tar(&self, tar: &Path, blake3: &Option<String>) -> Result<String> {
+        let Some(blake3) = blake3 else {
+            bail_other_err!(
+                "{:?} has no blake3 to be found offline, run \"repo capture-rev\" first",
+                tar.display()
+            );
+        };
+        store(&self.dir.join("sources"), blake3, tar)?;
+        Ok(blake3.clone())
+    }
+
+    /// Store the checked out commit of `dir` and its submodules
+    fn add_git(&self, dir: &Path, git: &str, logger: &PtyOut) -> Result<VendoredGit> {
+        let mut vendored = VendoredGit {
+            rev: git_output(dir, &["rev-parse", "HEAD"])?,
+            mirror: mirror_name(git),
+            submodules: BTreeMap::new(),
+        };
+        self.add_git_head(dir, &vendored.mirror, &vendored.rev, logger)?;
+
+        let submodules = git_output(
+            dir,
+            &[
+                "submodule",
+                "foreach",
+                "--recursive",
+                "--quiet",
+                r#"printf '%s\t%s\t%s\n' "$displaypath" "$(git rev-parse HEAD)" "$(git config --get remote.origin.url)""#,
+            ],
+        )?;
+        for line in submodules.lines() {
+            let mut split = line.split('\t');
+            let (Some(path), Some(rev), Some(url)) = (split.next(), split.next(), split.next())
+            else {
+                continue;
+            };
+            let mirror = mirror_name(url);
+            self.add_git_head(&dir.join(path), &mirror, rev, logger)?;
+            vendored.submodules.insert(path.to_string(), mirror);
+        }
+        Ok(vendored)
+    }
+
+    fn add_git_head(&self, dir: &Path, mirror: &str, rev: &str, logger: &PtyOut) -> Result<()> {
+        let mirror = self.dir.join("git").join(mirror);
+        if !mirror.is_dir() {
+            let mut command = Command::new("git");
+            command
+                .arg("init")
+                .arg("--bare")
+                .arg("--quiet")
+                .arg(&mirror);
+            run_command(command, logger)?;
+        }
+        let dir = dir
+            .canonicalize()
+            .map_err(wrap_io_err!(dir, "Canonicalizing git dir"))?;
+        // a branch for each commit, as clones only fetch branches
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&mirror);
+        command
+            .arg("fetch")
+            .arg("--quiet")
+            .arg(&dir)
+            .arg(format!("+HEAD:refs/heads/vendor/{rev}"));
+        run_command(command, logger)
+    }
+
+    fn add_cargo(
+        &self,
+        source_dir: &Path,
+        cargopath: Option<&String>,
+        logger: &PtyOut,
+    ) -> Result<()> {
+        let Some(mut command) = cargo_command() else {
+            bail_other_err!("Vendoring crates needs cargo in $REDOXER_TOOLCHAIN");
+        };
+        let mut source_dir = source_dir.to_path_buf();
+        if let Some(cargopath) = cargopath {
+            source_dir = source_dir.join(cargopath);
+        }
+        // crates of all recipes share one dir, as cargo finds them by name and version
+        command.arg("vendor");
+        command.arg("--versioned-dirs").arg("--no-delete");
+        command.arg("--manifest-path");
+        command.arg(source_dir.join("Cargo.toml"));
+        command.arg(self.dir.join("cargo"));
+        run_command(command, logger)
+    }
+
+    fn add_packages(&self, recipe: &CookRecipe, vendored: &mut VendoredRecipe) -> Result<()> {
+        let target_dir = recipe.target_dir();
+        let packages_dir = packages_dir(&self.dir, recipe);
+        create_dir(&packages_dir)?;
+        for package in recipe.recipe.get_packages_list() {
+            let (_, source_pkgar, source_toml) = package_source_paths(package, &target_dir);
+            for file in [source_pkgar, source_toml] {
+                let name = file.file_name().unwrap().to_string_lossy().to_string();
+                let dest = packages_dir.join(&name);
+                fs::copy(&file, &dest).map_err(wrap_io_err!(file, dest, "Copying package"))?;
+                vendored.packages.push(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the binary repo metadata, which remote recipes are checked against
+    pub fn add_remotes(&self) -> Result<()> {
+        let remotes_dir = Path::new("build/remotes");
+        if !remotes_dir.is_dir() {
+            return Ok(());
+        }
+        let dest = self.dir.join("remotes");
+        copy_dir_all(remotes_dir, &dest).map_err(wrap_io_err!(remotes_dir, dest, "Copying remotes"))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        serialize_and_write(&self.dir.join(MANIFEST_FILE), &self.manifest)
+    }
+}
+
+fn packages_dir(bundle_dir: &Path, recipe: &CookRecipe) -> PathBuf {
+    bundle_dir
+        .join("packages")
+        .join(recipe.target)
+        .join(recipe.name.name())
+}
+
+fn load_vendored(recipe: &CookRecipe) -> Result<Option<(PathBuf, VendoredRecipe)>> {
+    let Some(bundle_dir) = vendor_dir() else {
+        return Ok(None);
+    };
+    let mut manifest = VendorManifest::load(&bundle_dir)?;
+    Ok(manifest
+        .recipes
+        .remove(recipe.name.as_str())
+        .map(|vendored| (bundle_dir, vendored)))
+}
+
+/// Clone the vendored git source of `recipe` into `dir`, returns false if it's not vendored
+pub fn vendor_restore_git(
+    recipe: &CookRecipe,
+    git: &str,
+    dir: &Path,
+    logger: &PtyOut,
+) -> Result<bool> {
+    let Some((bundle_dir, vendored)) = load_vendored(recipe)? else {
+        return Ok(false);
+    };
+    let Some(vendored_git) = &vendored.git else {
+        return Ok(false);
+    };
+    restore_git(&bundle_dir, vendored_git, git, dir, logger)?;
+    Ok(true)
+}
+
+/// Clone vendored extra git sources which are missing from `source_dir`
+pub fn vendor_restore_extra_git(
+    recipe: &CookRecipe,
+    source_dir: &Path,
+    extra: &[ExtraSourceRecipe],
+    logger: &PtyOut,
+) -> Result<()> {
+    let Some((bundle_dir, vendored)) = load_vendored(recipe)? else {
+        return Ok(());
+    };
+    for e in extra {
+        let ExtraSource::Git { git, .. } = &e.source else {
+            continue;
+        };
+        let dir = source_dir.join(&e.dir);
+        if dir.join(".git").exists() {
+            continue;
+        }
+        if let Some(vendored_git) = vendored.extra_git.get(&e.dir) {
+            if dir.exists() {
+                remove_all(&dir)?;
+            }
+            restore_git(&bundle_dir, vendored_git, git, &dir, logger)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy a vendored package file of a remote recipe into `dest`, if it's missing
+pub fn vendor_restore_package(recipe: &CookRecipe, dest: &Path) -> Result<()> {
+    if dest.is_file() {
+        return Ok(());
+    }
+    let Some(bundle_dir) = vendor_dir() else {
+        return Ok(());
+    };
+    let Some(name) = dest.file_name() else {
+        return Ok(());
+    };
+    let src = packages_dir(&bundle_dir, recipe).join(name);
+    if src.is_file() {
+        fs::copy(&src, dest).map_err(wrap_io_err!(src, dest, "Copying package"))?;
+    }
+    Ok(())
+}
+
+/// Copy the vendored binary repo metadata into `remotes_dir`
+pub fn vendor_restore_remotes(remotes_dir: &Path) -> Result<()> {
+    let Some(bundle_dir) = vendor_dir() else {
+        return Ok(());
+    };
+    let src = bundle_dir.join("remotes");
+    if src.is_dir() {
+        copy_dir_all(&src, remotes_dir).map_err(wrap_io_err!(
+            src,
+            remotes_dir,
+            "Copying remotes"
+        ))?;
+    }
+    Ok(())
+}
+
+fn restore_git(
+    bundle_dir: &Path,
+    vendored: &VendoredGit,
+    git: &str,
+    dir: &Path,
+    logger: &PtyOut,
+) -> Result<()> {
+    let git_dir = bundle_dir.join("git");
+    let git_dir = git_dir
+        .canonicalize()
+        .map_err(wrap_io_err!(git_dir, "Canonicalizing bundle git dir"))?;
+    let mut command = Command::new("git");
+    command
+        .arg("clone")
+        .arg("--no-checkout")
+        .arg(git_dir.join(&vendored.mirror))
+        .arg(dir);
+    run_command(command, logger)?;
+
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir);
+    command.arg("checkout").arg(&vendored.rev);
+    run_command(command, logger)?;
+
+    // so fetching online later works as usual
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir);
+    command.arg("remote").arg("set-url").arg("origin").arg(git);
+    run_command(command, logger)?;
+
+    restore_submodules(dir, "", &vendored.submodules, &git_dir, logger)
+}
+
+/// Clone submodules from their bundle repos, recursively
+fn restore_submodules(
+    repo_dir: &Path,
+    prefix: &str,
+    submodules: &BTreeMap<String, String>,
+    git_dir: &Path,
+    logger: &PtyOut,
+) -> Result<()> {
+    if !repo_dir.join(".gitmodules").is_file() {
+        return Ok(());
+    }
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo_dir);
+    command
+        .arg("config")
+        .arg("-f")
+        .arg(".gitmodules")
+        .arg("--get-regexp")
+        .arg(r"^submodule\..*\.path$");
+    let output = command
+        .output()
+        .map_err(wrap_io_err!(repo_dir, "Running git config"))?;
+    // exit code 1 means nothing is found
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(Error::Command(command, output.status));
+    }
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((key, path)) = line.split_once(' ') else {
+            continue;
+        };
+        let Some(name) = key
+            .strip_prefix("submodule.")
+            .and_then(|k| k.strip_suffix(".path"))
+        else {
+            continue;
+        };
+        let display_path = if prefix.is_empty() {
+            path.to_string()
+        } else {
+            format!("{prefix}/{path}")
+        };
+        // not checked out when vendored, so not needed
+        let Some(mirror) = submodules.get(&display_path) else {
+            continue;
+        };
+
+        let mut command = Command::new("git");
+        command.arg("-C").arg(repo_dir);
+        command
+            .arg("config")
+            .arg(format!("submodule.{name}.url"))
+            .arg(git_dir.join(mirror));
+        run_command(command, logger)?;
+
+        let mut command = Command::new("git");
+        command.arg("-C").arg(repo_dir);
+        command
+            .arg("-c")
+            .arg("protocol.file.allow=always")
+            .arg("submodule")
+            .arg("update")
+            .arg("--init")
+            .arg("--")
+            .arg(path);
+        run_command(command, logger)?;
+
+        restore_submodules(
+            &repo_dir.join(path),
+            &display_path,
+            submodules,
+            git_dir,
+            logger,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use crate::cook::fs::git_output;
+    use crate::cook::vendor::{VendorBundle, restore_git};
+
+    #[test]
+    fn vendor_git_roundtrip() {
+        let root = std::env::temp_dir().join("temp_test_dir_vendor_git_roundtrip");
+        let _ = fs::remove_dir_all(&root);
+        let source_dir = root.join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("README"), "vendored").unwrap();
+        for args in [
+            &["init", "--quiet"][..],
+            &["add", "README"],
+            &["commit", "--quiet", "-m", "initial"],
+        ] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&source_dir)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let url = "https://example.com/foo.git";
+        let bundle = VendorBundle::open(&root.join("bundle")).unwrap();
+        let vendored = bundle.add_git(&source_dir, url, &None).unwrap();
+        assert_eq!(vendored.mirror, "example.com_foo.git");
+
+        let restored = root.join("restored");
+        restore_git(bundle.dir(), &vendored, url, &restored, &None).unwrap();
+        assert_eq!(
+            fs::read_to_string(restored.join("README")).unwrap(),
+            "vendored"
+        );
+        assert_eq!(
+            git_output(&restored, &["rev-parse", "HEAD"]).unwrap(),
+            vendored.rev
+        );
+        assert_eq!(
+            git_output(&restored, &["remote", "get-url", "origin"]).unwrap(),
+            url
+        );
+    }
+}
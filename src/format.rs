@@ -0,0 +1,395 @@
+use std::path::Path;
+
+use toml_edit::{Array, ArrayOfTables, Decor, DocumentMut, Item, RawString, Table, Value};
+
+use crate::lint::lint_unknown_keys;
+use crate::recipe::Recipe;
+use crate::{Error, Result, wrap_io_err};
+
+// This file contains the canonical formatting of recipe.toml, used by `repo fmt`.
+
+const ROOT_ORDER: &[&str] = &[
+    "extends",
+    "source",
+    "build",
+    "package",
+    "optional-packages",
+    "target",
+    "variants",
+    "test",
+];
+const SOURCE_ORDER: &[&str] = &[
+    "git",
+    "upstream",
+    "branch",
+    "tag",
+    "rev",
+    "shallow_clone",
+    "tar",
+    "blake3",
+    "sha256",
+    "sha512",
+    "same_as",
+    "path",
+    "patches",
+    "script",
+    "extra",
+];
+const EXTRA_ORDER: &[&str] = &["dir", "git", "branch", "rev", "tar", "blake3"];
+// template first and script last, as most recipes are written
+const BUILD_ORDER: &[&str] = &[
+    "template",
+    "dependencies",
+    "dev-dependencies",
+    "cargopath",
+    "cargoflags",
+    "cargopackages",
+    "cargoexamples",
+    "clearlocked",
+    "cargopackagesprefixed",
+    "librarytype",
+    "configureflags",
+    "cmakeflags",
+    "mesonflags",
+    "makepath",
+    "makeflags",
+    "install_target",
+    "gopackages",
+    "goflags",
+    "ldflags",
+    "pyprojectpath",
+    "pipflags",
+    "legacysetup",
+    "env",
+    "script",
+];
+const PACKAGE_ORDER: &[&str] = &[
+    "dependencies",
+    "version",
+    "description",
+    "license",
+    "homepage",
+    "upstream_url",
+    "maintainers",
+    "provides",
+    "conflicts",
+    "replaces",
+];
+const OPTIONAL_PACKAGE_ORDER: &[&str] = &[
+    "name",
+    "description",
+    "version",
+    "dependencies",
+    "files",
+    "exclude",
+];
+const TARGET_ORDER: &[&str] = &["description", "default", "build", "package", "patches"];
+const TARGET_BUILD_ORDER: &[&str] = &["dependencies", "dev-dependencies", "flags", "env"];
+
+/// Arrays of package names, which order does not matter
+const DEPENDENCY_KEYS: &[&str] = &[
+    "dependencies",
+    "dev-dependencies",
+    "provides",
+    "conflicts",
+    "replaces",
+];
+
+/// Format a recipe file, returning the new content if it's not formatted.
+/// The recipe must be valid without keys unknown to [`Recipe`], and formatting
+/// must not change its meaning.
+pub fn format_recipe_file(file: &Path) -> Result<Option<String>> {
+    let recipe = Recipe::new(&file.to_path_buf())?;
+    let content = std::fs::read_to_string(file).map_err(wrap_io_err!(file, "Reading recipe"))?;
+    let raw = content
+        .parse::<toml::Table>()
+        .map_err(|e| Error::Other(format!("{}: {e}", file.display())))?;
+    let unknown_keys = lint_unknown_keys(&raw, &recipe);
+    if !unknown_keys.is_empty() {
+        return Err(Error::Other(format!(
+            "{}: {}, fix them before formatting",
+            file.display(),
+            unknown_keys.join(", ")
+        )));
+    }
+    let formatted = format_recipe(&content)?;
+
+    let parse = |s: &str| {
+        s.parse::<toml::Value>()
+            .map(|mut v| {
+                sort_dependency_values(&mut v);
+                v
+            })
+            .map_err(|e| Error::Other(format!("{}: {e}", file.display())))
+    };
+    if parse(&content)? != parse(&formatted)? {
+        return Err(Error::Other(format!(
+            "{}: formatting would change the recipe",
+            file.display()
+        )));
+    }
+
+    Ok(if formatted != content {
+        Some(formatted)
+    } else {
+        None
+    })
+}
+
+/// Sort sections and keys in the canonical order, sort dependency lists and
+/// normalize string quoting, keeping comments in place
+pub fn format_recipe(content: &str) -> Result<String> {
+    let mut doc = content
+        .parse::<DocumentMut>()
+        .map_err(|e| Error::Other(format!("Parsing recipe: {e}")))?;
+    // the first table needs a blank line too after root keys, such as "extends"
+    let mut position = usize::from(doc.as_table().iter().any(|(_, item)| item.is_value()));
+    format_table(doc.as_table_mut(), &[], &mut position);
+    let mut formatted = doc.to_string();
+    formatted.truncate(formatted.trim_end().len());
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+fn key_order(path: &[&str]) -> &'static [&'static str] {
+    match path {
+        [] => ROOT_ORDER,
+        ["source"] => SOURCE_ORDER,
+        ["source", "extra"] => EXTRA_ORDER,
+        ["build"] => BUILD_ORDER,
+        ["package"] => PACKAGE_ORDER,
+        ["optional-packages"] => OPTIONAL_PACKAGE_ORDER,
+        ["target" | "variants", "*"] => TARGET_ORDER,
+        ["target" | "variants", "*", "build"] => TARGET_BUILD_ORDER,
+        _ => &[],
+    }
+}
+
+fn format_table(table: &mut Table, path: &[&str], position: &mut usize) {
+    let order = key_order(path);
+    let rank = |key: &str| order.iter().position(|k| *k == key).unwrap_or(order.len());
+    table.sort_values_by(|k1, _, k2, _| rank(k1.get()).cmp(&rank(k2.get())));
+
+    for (key, item) in table.iter_mut() {
+        let key = key.get().to_string();
+        let mut child_path = path.to_vec();
+        // tables in these are named by users
+        child_path.push(match path {
+            ["target" | "variants"] => "*",
+            _ => key.as_str(),
+        });
+        match item {
+            Item::Table(child) => {
+                if !child.is_dotted() {
+                    set_table_position(child, position);
+                }
+                format_table(child, &child_path, position);
+            }
+            Item::ArrayOfTables(children) => {
+                format_array_of_tables(children, &child_path, position)
+            }
+            Item::Value(value) => {
+                format_value(value);
+                if DEPENDENCY_KEYS.contains(&key.as_str())
+                    && let Some(array) = value.as_array_mut()
+                {
+                    sort_array(array);
+                }
+            }
+            Item::None => {}
+        }
+    }
+}
+
+fn format_array_of_tables(children: &mut ArrayOfTables, path: &[&str], position: &mut usize) {
+    for child in children.iter_mut() {
+        set_table_position(child, position);
+        format_table(child, path, position);
+    }
+}
+
+/// Tables are written in order of their position, separated by a blank line
+fn set_table_position(table: &mut Table, position: &mut usize) {
+    table.set_position(*position);
+    if !table.is_implicit() {
+        let prefix = table
+            .decor()
+            .prefix()
+            .and_then(|p| p.as_str())
+            .unwrap_or("");
+        let prefix = prefix.trim_start_matches('\n');
+        let prefix = if *position == 0 {
+            prefix.to_string()
+        } else {
+            format!("\n{prefix}")
+        };
+        table.decor_mut().set_prefix(prefix);
+        *position += 1;
+    }
+}
+
+fn format_value(value: &mut Value) {
+    match value {
+        Value::String(_) => normalize_string(value),
+        Value::Array(array) => array.iter_mut().for_each(format_value),
+        Value::InlineTable(table) => table.iter_mut().for_each(|(_, v)| format_value(v)),
+        _ => {}
+    }
+}
+
+/// Use basic strings, and multi-line basic strings starting on a new line where possible
+fn normalize_string(value: &mut Value) {
+    let Some(s) = value.as_str() else {
+        return;
+    };
+    let normalized = if !s.contains('\n') {
+        Value::from(s)
+    } else if !s.contains(['\\', '\r'])
+        && !s.contains("\"\"\"")
+        && !s.ends_with('"')
+        && !s.chars().any(|c| c.is_control() && c != '\n' && c != '\t')
+    {
+        match format!("\"\"\"\n{s}\"\"\"").parse::<Value>() {
+            Ok(v) => v,
+            Err(_) => return,
+        }
+    } else {
+        return;
+    };
+    if normalized.as_str() != Some(s) {
+        return;
+    }
+    let decor = value.decor().clone();
+    *value = normalized;
+    *value.decor_mut() = decor;
+}
+
+/// Sort values while keeping whitespace in place.
+/// Arrays with comments are left as is, as a comment can't be reliably tied to a value.
+fn sort_array(array: &mut Array) {
+    let is_comment =
+        |s: Option<&RawString>| s.and_then(|s| s.as_str()).is_some_and(|s| s.contains('#'));
+    if is_comment(Some(array.trailing()))
+        || array
+            .iter()
+            .any(|v| is_comment(v.decor().prefix()) || is_comment(v.decor().suffix()))
+    {
+        return;
+    }
+    let decors: Vec<Decor> = array.iter().map(|v| v.decor().clone()).collect();
+    let mut values: Vec<Value> = array.iter().cloned().collect();
+    values.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+    array.clear();
+    for (mut value, decor) in values.into_iter().zip(decors) {
+        *value.decor_mut() = decor;
+        array.push_formatted(value);
+    }
+}
+
+fn sort_dependency_values(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                if DEPENDENCY_KEYS.contains(&key.as_str())
+                    && let toml::Value::Array(array) = value
+                {
+                    array.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                }
+                sort_dependency_values(value);
+            }
+        }
+        toml::Value::Array(array) => array.iter_mut().for_each(sort_dependency_values),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::{format_recipe, format_recipe_file};
+
+    #[test]
+    fn format_recipe_unknown_keys() {
+        let dir = std::env::temp_dir().join("temp_test_dir_format_recipe_unknown_keys");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("recipe.toml");
+        std::fs::write(
+            &file,
+            "[build]\ntemplate = \"cargo\"\ncargoflag = [\"--all\"]\n",
+        )
+        .unwrap();
+
+        let err = format_recipe_file(&file).unwrap_err().to_string();
+        assert!(err.contains("unknown key \"build.cargoflag\""), "{err}");
+
+        std::fs::write(
+            &file,
+            "[build]\ncargoflags = [\"--all\"]\ntemplate = \"cargo\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            format_recipe_file(&file).unwrap().as_deref(),
+            Some("[build]\ntemplate = \"cargo\"\ncargoflags = [\"--all\"]\n")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn format_recipe_canonical() {
+        let recipe = r#"# comment about the package
+[package]
+dependencies = ["zlib", "openssl3"]
+
+[build]
+script = '''
+make install
+'''
+dependencies = [
+    "zstd",
+    "libpng",
+]
+dev-dependencies = [
+    "rust", # for the docs
+    "cmake",
+]
+template = 'custom'
+
+[source]
+blake3 = "abc"
+tar = "https://example.com/foo-1.0.tar.xz"
+patches = [
+    "b.patch", # applied last
+    "a.patch",
+]
+"#;
+        let expected = r#"[source]
+tar = "https://example.com/foo-1.0.tar.xz"
+blake3 = "abc"
+patches = [
+    "b.patch", # applied last
+    "a.patch",
+]
+
+[build]
+template = "custom"
+dependencies = [
+    "libpng",
+    "zstd",
+]
+dev-dependencies = [
+    "rust", # for the docs
+    "cmake",
+]
+script = """
+make install
+"""
+
+# comment about the package
+[package]
+dependencies = ["openssl3", "zlib"]
+"#;
+        let formatted = format_recipe(recipe).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_recipe(&formatted).unwrap(), formatted);
+    }
+}
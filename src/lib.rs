@@ -1,6 +1,10 @@
 pub mod config;
 pub mod cook;
+pub mod format;
+pub mod lint;
+pub mod outdated;
 pub mod recipe;
+pub mod scaffold;
 pub mod staged_pkg;
 pub mod web;
 
@@ -48,6 +52,13 @@ pub enum Error {
     Package(pkg::PackageError),
     PackageBackend(pkg::backend::Error),
     Pkgar(pkgar::Error),
+    /// A dependency version does not satisfy the constraint of the last recipe in the chain
+    Constraint {
+        name: pkg::PackageName,
+        version: String,
+        constraint: recipe::VersionConstraint,
+        chain: Vec<pkg::PackageName>,
+    },
     Options(String),
     Other(String),
 }
@@ -101,6 +112,20 @@ impl Display for Error {
                 write!(f, "Package backend error: {}", package_error)
             }
             Error::Pkgar(error) => write!(f, "Package archive error: {}", error),
+            Error::Constraint {
+                name,
+                version,
+                constraint,
+                chain,
+            } => {
+                let chain: Vec<&str> = chain.iter().map(|n| n.as_str()).collect();
+                write!(
+                    f,
+                    "{} {version} does not satisfy \"{constraint}\", required by {}",
+                    name.as_str(),
+                    chain.join(" -> ")
+                )
+            }
             Error::Other(context) | Error::Options(context) => {
                 write!(f, "{context}")
             }
@@ -0,0 +1,342 @@
+use std::fs;
+use std::path::Path;
+
+use pkg::PackageError;
+
+use crate::recipe::{ExtraSource, ExtraSourceRecipe, Recipe, SourceRecipe};
+use crate::staged_pkg;
+
+// This file contains static checks of recipe.toml, used by `repo lint`.
+
+/// Check a recipe directory, returning list of human readable issues.
+/// An empty list means the recipe is fine.
+pub fn lint_recipe(dir: &Path) -> Vec<String> {
+    let file = dir.join("recipe.toml");
+    let recipe = match Recipe::new(&file) {
+        Ok(recipe) => recipe,
+        Err(e) => return vec![format!("{}", e)],
+    };
+    let raw = match fs::read_to_string(&file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| s.parse::<toml::Table>().map_err(|e| e.to_string()))
+    {
+        Ok(raw) => raw,
+        Err(e) => return vec![e],
+    };
+
+    let mut issues = lint_unknown_keys(&raw, &recipe);
+
+    match &recipe.source {
+        Some(SourceRecipe::Git { patches, extra, .. }) => {
+            lint_patches(dir, patches, &mut issues);
+            lint_extra(extra, &mut issues);
+        }
+        Some(SourceRecipe::Tar {
+            tar,
+            blake3,
+            patches,
+            extra,
+            ..
+        }) => {
+            if blake3.is_none() {
+                issues.push(format!("tar source {:?} has no blake3 checksum", tar));
+            }
+            lint_patches(dir, patches, &mut issues);
+            lint_extra(extra, &mut issues);
+        }
+        Some(SourceRecipe::SameAs { same_as }) => {
+            if !dir.join(same_as).join("recipe.toml").is_file() {
+                issues.push(format!("same_as {:?} does not point to a recipe", same_as));
+            }
+        }
+        Some(SourceRecipe::Path { .. }) | None => {}
+    }
+
+    for target in recipe.target.values() {
+        lint_patches(dir, &target.patches, &mut issues);
+    }
+
+    let meta = &recipe.package;
+    if let Some(license) = &meta.license
+        && !is_valid_spdx(license)
+    {
+        issues.push(format!(
+            "license {:?} is not a valid SPDX expression",
+            license
+        ));
+    }
+    for (key, url) in [
+        ("homepage", &meta.homepage),
+        ("upstream_url", &meta.upstream_url),
+    ] {
+        if let Some(url) = url
+            && !url.starts_with("https://")
+            && !url.starts_with("http://")
+        {
+            issues.push(format!("{} {:?} is not a http(s) URL", key, url));
+        }
+    }
+    if meta.maintainers.iter().any(|m| m.trim().is_empty()) {
+        issues.push("maintainers contains an empty name".to_string());
+    }
+
+    let optional_deps = recipe
+        .optional_packages
+        .iter()
+        .flat_map(|p| p.dependencies.iter());
+    let target_deps = recipe.target.values().flat_map(|t| {
+        t.build
+            .dependencies
+            .items()
+            .iter()
+            .chain(t.build.dev_dependencies.items())
+            .chain(t.package.dependencies.items())
+    });
+    for dep in recipe
+        .build
+        .dependencies
+        .iter()
+        .chain(recipe.build.dev_dependencies.iter())
+        .chain(recipe.package.dependencies.iter())
+        .chain(optional_deps)
+        .chain(target_deps)
+    {
+        // empty name refers to optional packages of this recipe
+        if dep.name().is_empty() {
+            continue;
+        }
+        match staged_pkg::resolve(dep) {
+            Ok(_) => {}
+            Err(PackageError::PackageNotFound(_)) => {
+                issues.push(format!("dependency {:?} is not found", dep.as_str()));
+            }
+            Err(PackageError::DependencyInvalid(_)) => {
+                let providers: Vec<&str> = staged_pkg::find_providers(dep.name())
+                    .iter()
+                    .map(|p| p.as_str())
+                    .collect();
+                issues.push(format!(
+                    "dependency {:?} is provided by multiple recipes, depend on one of {}",
+                    dep.as_str(),
+                    providers.join(", ")
+                ));
+            }
+            Err(e) => issues.push(format!("dependency {:?}: {e}", dep.as_str())),
+        }
+    }
+
+    issues
+}
+
+fn lint_patches(dir: &Path, patches: &[String], issues: &mut Vec<String>) {
+    for patch in patches {
+        if !dir.join(patch).is_file() {
+            issues.push(format!("patch file {:?} is not found", patch));
+        }
+    }
+}
+
+fn lint_extra(extra: &[ExtraSourceRecipe], issues: &mut Vec<String>) {
+    for e in extra {
+        if let ExtraSource::Tar { tar, blake3: None } = &e.source {
+            issues.push(format!(
+                "extra source {:?} tar {:?} has no blake3 checksum",
+                e.dir, tar
+            ));
+        }
+    }
+}
+
+/// Check the syntax of an SPDX license expression, such as "(MIT OR Apache-2.0) AND BSD-3-Clause".
+/// License identifiers are not checked against the SPDX license list.
+pub fn is_valid_spdx(expr: &str) -> bool {
+    let spaced = expr.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    let mut pos = 0;
+    spdx_expr(&tokens, &mut pos) && pos == tokens.len()
+}
+
+fn spdx_expr(tokens: &[&str], pos: &mut usize) -> bool {
+    loop {
+        if !spdx_term(tokens, pos) {
+            return false;
+        }
+        match tokens.get(*pos) {
+            Some(&"AND") | Some(&"OR") => *pos += 1,
+            _ => return true,
+        }
+    }
+}
+
+fn spdx_term(tokens: &[&str], pos: &mut usize) -> bool {
+    match tokens.get(*pos) {
+        Some(&"(") => {
+            *pos += 1;
+            if !spdx_expr(tokens, pos) || tokens.get(*pos) != Some(&")") {
+                return false;
+            }
+            *pos += 1;
+            true
+        }
+        Some(id) if is_spdx_id(id) => {
+            *pos += 1;
+            if tokens.get(*pos) == Some(&"WITH") {
+                *pos += 1;
+                if !tokens.get(*pos).is_some_and(|id| is_spdx_id(id)) {
+                    return false;
+                }
+                *pos += 1;
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_spdx_id(id: &str) -> bool {
+    let id = id.strip_suffix('+').unwrap_or(id);
+    !id.is_empty()
+        && !matches!(id, "AND" | "OR" | "WITH")
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':'))
+}
+
+/// Report keys in the raw toml that are silently dropped by deserializing into [`Recipe`]
+pub fn lint_unknown_keys(raw: &toml::Table, recipe: &Recipe) -> Vec<String> {
+    let mut issues = Vec::new();
+    match toml::Value::try_from(recipe) {
+        Ok(toml::Value::Table(known)) => walk_unknown_keys("", raw, &known, &mut issues),
+        Ok(_) => unreachable!(),
+        Err(e) => issues.push(format!("unable to serialize recipe: {}", e)),
+    }
+    issues
+}
+
+fn walk_unknown_keys(
+    prefix: &str,
+    raw: &toml::Table,
+    known: &toml::Table,
+    issues: &mut Vec<String>,
+) {
+    for (key, raw_value) in raw {
+        let path = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        let Some(known_value) = known.get(key) else {
+            issues.push(format!("unknown key {:?}", path));
+            continue;
+        };
+        walk_unknown_values(&path, raw_value, known_value, issues);
+    }
+}
+
+fn walk_unknown_values(
+    path: &str,
+    raw: &toml::Value,
+    known: &toml::Value,
+    issues: &mut Vec<String>,
+) {
+    match (raw, known) {
+        (toml::Value::Table(raw), toml::Value::Table(known)) => {
+            walk_unknown_keys(path, raw, known, issues);
+        }
+        (toml::Value::Array(raw), toml::Value::Array(known)) => {
+            for (i, (raw, known)) in raw.iter().zip(known.iter()).enumerate() {
+                walk_unknown_values(&format!("{path}[{i}]"), raw, known, issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::{is_valid_spdx, lint_unknown_keys};
+    use crate::recipe::Recipe;
+
+    fn lint_str(s: &str) -> Vec<String> {
+        let raw: toml::Table = s.parse().unwrap();
+        let recipe: Recipe = toml::from_str(s).unwrap();
+        let mut issues = lint_unknown_keys(&raw, &recipe);
+        issues.sort();
+        issues
+    }
+
+    #[test]
+    fn known_keys() {
+        let issues = lint_str(
+            r#"
+            [source]
+            tar = "https://example.com/foo-1.0.tar.gz"
+            patches = ["redox.patch"]
+
+            [build]
+            template = "cargo"
+            dependencies = ["openssl3"]
+
+            [[optional-packages]]
+            name = "dev"
+            files = ["/usr/include/**", "/usr/lib/*.a"]
+            exclude = ["/usr/include/foo/private.h"]
+            description = "Development files for foo"
+            version = "1.0"
+        "#,
+        );
+        assert_eq!(issues, Vec::<String>::new());
+    }
+
+    #[test]
+    fn spdx_expressions() {
+        for expr in [
+            "MIT",
+            "GPL-2.0-or-later",
+            "MIT OR Apache-2.0",
+            "(MIT OR Apache-2.0) AND BSD-3-Clause",
+            "GPL-2.0+ WITH Classpath-exception-2.0",
+            "LicenseRef-redox",
+        ] {
+            assert!(is_valid_spdx(expr), "{expr:?} should be valid");
+        }
+        for expr in [
+            "",
+            "MIT OR",
+            "MIT Apache-2.0",
+            "(MIT",
+            "MIT WITH",
+            "MIT/Apache-2.0",
+        ] {
+            assert!(!is_valid_spdx(expr), "{expr:?} should be invalid");
+        }
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let issues = lint_str(
+            r#"
+            [source]
+            git = "https://gitlab.redox-os.org/redox-os/acid.git"
+            blake3 = "0000"
+
+            [build]
+            template = "custom"
+            script = "make"
+            dependecies = ["openssl3"]
+
+            [[optional-packages]]
+            name = "dev"
+            file = ["/usr/include"]
+        "#,
+        );
+        assert_eq!(
+            issues,
+            vec![
+                "unknown key \"build.dependecies\"".to_string(),
+                "unknown key \"optional-packages[0].file\"".to_string(),
+                "unknown key \"source.blake3\"".to_string(),
+            ]
+        );
+    }
+}
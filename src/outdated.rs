@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use regex::Regex;
+
+use crate::config::translate_mirror;
+use crate::cook::download::download_string;
+use crate::recipe::{CookRecipe, SourceRecipe, VersionExtractor, compare_versions};
+use crate::{Error, Result, wrap_io_err};
+
+// This file contains upstream version checks of recipes, used by `repo outdated`.
+
+/// Current version of a recipe and newer versions found upstream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outdated {
+    pub current: String,
+    /// Newer versions, sorted from the oldest
+    pub newer: Vec<String>,
+}
+
+impl Outdated {
+    pub fn latest(&self) -> Option<&str> {
+        self.newer.last().map(|s| s.as_str())
+    }
+}
+
+/// Check the upstream of a recipe source for newer versions.
+/// Returns None if there's no upstream to check or the current version is unknown.
+///
+/// Git sources list tags of `upstream` (or `git`) and tar sources list the directory
+/// of the tar URL, both of which can be local paths or "file://" URLs.
+pub fn check_recipe(recipe: &CookRecipe) -> Result<Option<Outdated>> {
+    let (current, versions) = match &recipe.recipe.source {
+        Some(SourceRecipe::Git {
+            git, upstream, rev, ..
+        }) => {
+            let tags = list_git_tags(upstream.as_ref().unwrap_or(git))?;
+            // a rev pinned at a tag is more accurate than the guessed version
+            let current = rev
+                .as_ref()
+                .and_then(|rev| tags.iter().find(|(_, commit)| *commit == rev))
+                .map(|(version, _)| version.clone())
+                .or_else(|| recipe.guess_version());
+            (current, tags.into_keys().collect())
+        }
+        Some(SourceRecipe::Tar { tar, .. }) => (recipe.guess_version(), list_tar_versions(tar)?),
+        _ => return Ok(None),
+    };
+    let Some(current) = current.filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    Ok(Some(Outdated {
+        newer: newer_versions(&current, versions),
+        current,
+    }))
+}
+
+fn newer_versions(current: &str, versions: Vec<String>) -> Vec<String> {
+    let mut newer: Vec<String> = versions
+        .into_iter()
+        .filter(|v| compare_versions(v, current).is_gt())
+        .collect();
+    newer.sort_by(|a, b| compare_versions(a, b));
+    newer.dedup_by(|a, b| compare_versions(a, b).is_eq());
+    newer
+}
+
+/// List versions of remote tags, mapped to their commit
+fn list_git_tags(url: &str) -> Result<BTreeMap<String, String>> {
+    let mut command = Command::new("git");
+    command.arg("ls-remote").arg("--tags").arg(url);
+    let output = command
+        .output()
+        .map_err(wrap_io_err!("Running git ls-remote"))?;
+    if !output.status.success() {
+        return Err(Error::Command(command, output.status));
+    }
+
+    let re = VersionExtractor::new();
+    let mut tags = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((commit, tag)) = line.split_once('\t') else {
+            continue;
+        };
+        let Some(tag) = tag.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        // annotated tags are listed twice, the peeled one points to the commit
+        let (tag, peeled) = match tag.strip_suffix("^{}") {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let Some(version) = re.extract_ver(tag) else {
+            continue;
+        };
+        if peeled || !tags.contains_key(&version) {
+            tags.insert(version, commit.to_string());
+        }
+    }
+    Ok(tags)
+}
+
+/// List versions of files named like the tar in the directory listing of its URL
+fn list_tar_versions(tar: &str) -> Result<Vec<String>> {
+    let Some((dir, file_name)) = tar.rsplit_once('/') else {
+        return Ok(Vec::new());
+    };
+    let Some(version) = VersionExtractor::new().extract_ver(file_name) else {
+        return Ok(Vec::new());
+    };
+    let (prefix, suffix) = file_name.split_once(&version).unwrap();
+    let file_regex = Regex::new(&format!(
+        r"{}(\d+(?:\.\d+)*){}",
+        regex::escape(prefix),
+        regex::escape(suffix)
+    ))
+    .unwrap();
+
+    let local_dir = match dir.strip_prefix("file://") {
+        Some(path) => Some(path),
+        None if !dir.contains("://") => Some(dir),
+        None => None,
+    };
+    let listing = if let Some(path) = local_dir {
+        let mut names = Vec::new();
+        let path = Path::new(path);
+        for entry in fs::read_dir(path).map_err(wrap_io_err!(path, "Reading dir"))? {
+            let entry = entry.map_err(wrap_io_err!(path, "Reading dir entry"))?;
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.join("\n")
+    } else {
+        download_string(&translate_mirror(&format!("{dir}/")))?
+    };
+
+    Ok(file_regex
+        .captures_iter(&listing)
+        .map(|caps| caps[1].to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::Command;
+
+    use pkg::PackageName;
+
+    use crate::outdated::{Outdated, check_recipe};
+    use crate::recipe::{CookRecipe, Recipe};
+
+    fn cook_recipe(toml: &str) -> CookRecipe {
+        let recipe: Recipe = toml::from_str(toml).unwrap();
+        CookRecipe::new(
+            PackageName::new("foo").unwrap(),
+            Default::default(),
+            recipe,
+            &Default::default(),
+        )
+        .unwrap()
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn outdated_git_tags() {
+        let root = std::env::temp_dir().join("temp_test_dir_outdated_git_tags");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["commit", "-q", "--allow-empty", "-m", "first"]);
+        git(&root, &["tag", "v1.0.0"]);
+        let pinned = git(&root, &["rev-parse", "HEAD"]);
+        git(&root, &["commit", "-q", "--allow-empty", "-m", "second"]);
+        git(&root, &["tag", "-a", "v1.2.0", "-m", "annotated"]);
+        git(&root, &["tag", "not-a-version"]);
+
+        let recipe = cook_recipe(&format!(
+            "[source]\ngit = \"file://{}\"\nrev = \"{pinned}\"\n",
+            root.display()
+        ));
+        assert_eq!(
+            check_recipe(&recipe).unwrap(),
+            Some(Outdated {
+                current: "1.0.0".to_string(),
+                newer: vec!["1.2.0".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn outdated_tar_listing() {
+        let root = std::env::temp_dir().join("temp_test_dir_outdated_tar_listing");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        for name in [
+            "foo-0.9.tar.xz",
+            "foo-1.0.tar.xz",
+            "foo-1.10.tar.xz",
+            "foo-1.2.tar.xz",
+            "foo-1.2.tar.gz",
+            "bar-3.0.tar.xz",
+        ] {
+            std::fs::write(root.join(name), "").unwrap();
+        }
+
+        let recipe = cook_recipe(&format!(
+            "[source]\ntar = \"file://{}/foo-1.0.tar.xz\"\n",
+            root.display()
+        ));
+        let outdated = check_recipe(&recipe).unwrap().unwrap();
+        assert_eq!(outdated.current, "1.0");
+        assert_eq!(outdated.newer, vec!["1.2".to_string(), "1.10".to_string()]);
+        assert_eq!(outdated.latest(), Some("1.10"));
+    }
+}
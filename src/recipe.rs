@@ -1,8 +1,10 @@
 use std::{
-    collections::BTreeSet,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
     convert::TryInto,
-    fs,
+    fmt, fs,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use pkg::{PackageError, PackageName};
@@ -37,6 +39,9 @@ pub enum SourceRecipe {
         /// The optional branch of the git repository to track, such as master. Please specify to
         /// make updates to the rev easier
         branch: Option<String>,
+        /// The optional tag of the git repository to use for builds, such as v1.2.3. It is
+        /// resolved to a commit at fetch time and used as the package version
+        tag: Option<String>,
         /// The optional revision of the git repository to use for builds. Please specify for
         /// reproducible builds
         rev: Option<String>,
@@ -47,22 +52,79 @@ pub enum SourceRecipe {
         patches: Vec<String>,
         /// Optional script to run to prepare the source
         script: Option<String>,
+        /// Additional sources to fetch into subdirectories of the source
+        #[serde(default)]
+        extra: Vec<ExtraSourceRecipe>,
     },
     /// A tar file source
     Tar {
-        /// The URL of a tar source
+        /// The URL of a tar source. Zip and 7z archives are detected and extracted as well
         tar: String,
         /// The optional blake3 sum of the tar file. Please specify this to make reproducible
         /// builds more reliable
         blake3: Option<String>,
+        /// The optional sha256 sum of the tar file, as commonly published by upstream
+        sha256: Option<String>,
+        /// The optional sha512 sum of the tar file, as commonly published by upstream
+        sha512: Option<String>,
         /// A list of patch files to apply to the source
         #[serde(default)]
         patches: Vec<String>,
         /// Optional script to run to prepare the source, such as ./autogen.sh
         script: Option<String>,
+        /// Additional sources to fetch into subdirectories of the source
+        #[serde(default)]
+        extra: Vec<ExtraSourceRecipe>,
     },
 }
 
+/// An additional source, such as data files or a test suite, which is fetched
+/// before patches of the main source are applied
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExtraSourceRecipe {
+    /// The subdirectory of the source dir to fetch into
+    pub dir: String,
+    #[serde(flatten)]
+    pub source: ExtraSource,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ExtraSource {
+    /// A git repository source
+    Git {
+        /// The URL for the git repository
+        git: String,
+        /// The optional branch of the git repository to clone
+        branch: Option<String>,
+        /// The optional revision of the git repository to checkout. Please specify for
+        /// reproducible builds, otherwise it won't be updated after the first clone
+        rev: Option<String>,
+    },
+    /// A tar file source
+    Tar {
+        /// The URL of a tar source
+        tar: String,
+        /// The optional blake3 sum of the tar file
+        blake3: Option<String>,
+    },
+}
+
+impl ExtraSourceRecipe {
+    /// Identifier of this source as declared in the recipe
+    pub fn ident(&self) -> String {
+        let ident = match &self.source {
+            ExtraSource::Git { rev, branch, .. } => rev.as_ref().or(branch.as_ref()),
+            ExtraSource::Tar { blake3, .. } => blake3.as_ref(),
+        };
+        format!(
+            "{}:{}",
+            self.dir,
+            ident.map(|s| s.as_str()).unwrap_or("no_ident")
+        )
+    }
+}
+
 /// Specifies how to build a recipe
 #[derive(Debug, Clone, Deserialize, PartialEq, Serialize)]
 #[serde(tag = "template")]
@@ -99,6 +161,20 @@ pub enum BuildKind {
         #[serde(default)]
         cargopackagesprefixed: bool,
     },
+    /// Will build and install C library of a Rust crate using cargo-c
+    #[serde(rename = "cargo-c")]
+    CargoC {
+        /// Path relative to source dir, where to run Cargo
+        #[serde(default)]
+        cargopath: Option<String>,
+        /// Additional flags to cargo
+        #[serde(default)]
+        cargoflags: Vec<String>,
+        /// Library type to build, either "staticlib" or "cdylib".
+        /// Default to both, and always "staticlib" if target does not support dynamic linking.
+        #[serde(default)]
+        librarytype: Option<String>,
+    },
     /// Will build and install using configure and make
     #[serde(rename = "configure")]
     Configure {
@@ -117,6 +193,31 @@ pub enum BuildKind {
         #[serde(default)]
         mesonflags: Vec<String>,
     },
+    /// Will build and install using a plain Makefile
+    #[serde(rename = "make")]
+    Make {
+        /// Path relative to source dir, where to run make
+        #[serde(default)]
+        makepath: Option<String>,
+        #[serde(default)]
+        makeflags: Vec<String>,
+        /// Make target used to install into stage, default is "install"
+        #[serde(default)]
+        install_target: Option<String>,
+    },
+    /// Will build and install using go
+    #[serde(rename = "go")]
+    Go {
+        /// List of packages to build, relative to source dir. Default is the main package.
+        #[serde(default)]
+        gopackages: Vec<String>,
+        /// Additional flags to go build
+        #[serde(default)]
+        goflags: Vec<String>,
+        /// Additional flags to go linker, such as "-X main.version=1.0"
+        #[serde(default)]
+        ldflags: Vec<String>,
+    },
     /// Will build and install using python pip
     #[serde(rename = "python")]
     Python {
@@ -144,6 +245,10 @@ pub struct BuildRecipe {
     pub dependencies: Vec<PackageName>,
     #[serde(rename = "dev-dependencies")]
     pub dev_dependencies: Vec<PackageName>,
+    /// Environment variables exported before running the template,
+    /// `${COOKBOOK_*}` references in values are expanded. CPPFLAGS, LDFLAGS
+    /// and RUSTFLAGS are prepended to the flags set up by cookbook
+    pub env: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
@@ -152,6 +257,20 @@ pub struct PackageRecipe {
     pub dependencies: Vec<PackageName>,
     pub version: Option<String>,
     pub description: Option<String>,
+    /// SPDX license expression, such as "MIT OR Apache-2.0"
+    pub license: Option<String>,
+    /// URL of the project website
+    pub homepage: Option<String>,
+    /// URL of the upstream source, if the source is a fork or a mirror
+    pub upstream_url: Option<String>,
+    /// People responsible for this recipe, such as "Jane Doe <jane@example.com>"
+    pub maintainers: Vec<String>,
+    /// Virtual package names that this package can be depended on as, such as "sh"
+    pub provides: Vec<PackageName>,
+    /// Packages that can't be installed into the same sysroot as this package
+    pub conflicts: Vec<PackageName>,
+    /// Old names of this package, dependencies on them are resolved to this package
+    pub replaces: Vec<PackageName>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
@@ -159,13 +278,104 @@ pub struct PackageRecipe {
 pub struct OptionalPackageRecipe {
     pub name: String,
     pub dependencies: Vec<PackageName>,
+    /// Globs of stage files to move into this package
     pub files: Vec<String>,
+    /// Globs of stage files to keep out of this package, even if matched by `files`
+    pub exclude: Vec<String>,
+    pub description: Option<String>,
+    /// Version of this package, default to the version of the main package
+    pub version: Option<String>,
+}
+
+/// Additions to (or replacements of) the recipe that only apply to a specific target
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TargetRecipe {
+    pub build: TargetBuildRecipe,
+    pub package: TargetPackageRecipe,
+    /// Patch files applied after the source patches. Note that the source dir is shared
+    /// between targets, so switching target will reapply patches.
+    pub patches: Vec<String>,
+}
+
+/// A list appended to the one in the main recipe, or `{ replace = [...] }` to replace it
+#[derive(Debug, Clone, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum OverrideList<T> {
+    Extend(Vec<T>),
+    Replace { replace: Vec<T> },
+}
+
+impl<T> Default for OverrideList<T> {
+    fn default() -> Self {
+        OverrideList::Extend(Vec::new())
+    }
+}
+
+impl<T> OverrideList<T> {
+    pub fn items(&self) -> &[T] {
+        match self {
+            OverrideList::Extend(items) | OverrideList::Replace { replace: items } => items,
+        }
+    }
+
+    /// Whether applying this list leaves the main recipe unchanged
+    pub fn is_noop(&self) -> bool {
+        matches!(self, OverrideList::Extend(items) if items.is_empty())
+    }
+
+    pub fn apply(self, list: &mut Vec<T>) {
+        match self {
+            OverrideList::Extend(items) => list.extend(items),
+            OverrideList::Replace { replace } => *list = replace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TargetBuildRecipe {
+    pub dependencies: OverrideList<PackageName>,
+    #[serde(rename = "dev-dependencies")]
+    pub dev_dependencies: OverrideList<PackageName>,
+    /// Flags appended to the template flags, such as "cargoflags" or "configureflags"
+    pub flags: OverrideList<String>,
+    /// Environment variables added to (or replacing) the ones in `[build.env]`
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TargetPackageRecipe {
+    pub dependencies: OverrideList<PackageName>,
+}
+
+/// Specifies how to test a recipe after it is built
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TestRecipe {
+    /// Shell script to run against the stage dir
+    pub script: String,
+}
+
+/// A named build option, enabled per package from the filesystem config as "source+name"
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct VariantRecipe {
+    pub description: Option<String>,
+    /// Whether this variant is enabled unless disabled with "source+!name"
+    pub default: bool,
+    pub build: TargetBuildRecipe,
+    pub package: TargetPackageRecipe,
+    pub patches: Vec<String>,
 }
 
 /// Everything required to build a Redox package
 #[derive(Debug, Clone, Default, Deserialize, PartialEq, Serialize)]
 #[serde(default)]
 pub struct Recipe {
+    /// Relative path to a parent recipe (or its recipe dir) to deep-merge this recipe onto
+    pub extends: Option<String>,
     /// Specifies how to download the source for this recipe
     pub source: Option<SourceRecipe>,
     /// Specifies how to build this recipe
@@ -175,6 +385,63 @@ pub struct Recipe {
     /// Specifies optional packages based from this recipe
     #[serde(rename = "optional-packages")]
     pub optional_packages: Vec<OptionalPackageRecipe>,
+    /// Specifies additions for a target triple, applied when loaded as [`CookRecipe`]
+    pub target: BTreeMap<String, TargetRecipe>,
+    /// Specifies how to test this recipe, used in `repo test`
+    pub test: Option<TestRecipe>,
+    /// Specifies build options that can be enabled from the filesystem config
+    pub variants: BTreeMap<String, VariantRecipe>,
+    /// Names of variants enabled when loaded as [`CookRecipe`]
+    #[serde(skip)]
+    pub enabled_variants: Vec<String>,
+    /// Parent recipe files merged via `extends`, nearest first
+    #[serde(skip)]
+    pub parent_files: Vec<PathBuf>,
+    /// Version constraints of dependencies such as "openssl3 >= 3.0"
+    #[serde(skip)]
+    pub constraints: RecipeConstraints,
+}
+
+/// Version constraints of each dependency list, keyed by recipe name without "host:"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecipeConstraints {
+    pub build: BTreeMap<String, VersionConstraint>,
+    pub dev: BTreeMap<String, VersionConstraint>,
+    pub package: BTreeMap<String, VersionConstraint>,
+    /// Keyed by optional package name
+    pub optional: BTreeMap<String, BTreeMap<String, VersionConstraint>>,
+    /// Constraints of `[target.*]` sections, added when the target is applied
+    pub target: BTreeMap<String, RecipeConstraints>,
+    /// Constraints of `[variants.*]` sections, added when the variant is enabled
+    pub variants: BTreeMap<String, RecipeConstraints>,
+}
+
+impl SourceRecipe {
+    /// Patch files to apply, if this source kind supports patches
+    pub fn patches_mut(&mut self) -> Option<&mut Vec<String>> {
+        match self {
+            SourceRecipe::Git { patches, .. } | SourceRecipe::Tar { patches, .. } => Some(patches),
+            SourceRecipe::SameAs { .. } | SourceRecipe::Path { .. } => None,
+        }
+    }
+}
+
+impl BuildKind {
+    /// Additional flags to the template build tool, if this template has one
+    pub fn flags_mut(&mut self) -> Option<&mut Vec<String>> {
+        match self {
+            BuildKind::Cargo { cargoflags, .. } | BuildKind::CargoC { cargoflags, .. } => {
+                Some(cargoflags)
+            }
+            BuildKind::Configure { configureflags } => Some(configureflags),
+            BuildKind::Cmake { cmakeflags } => Some(cmakeflags),
+            BuildKind::Meson { mesonflags } => Some(mesonflags),
+            BuildKind::Make { makeflags, .. } => Some(makeflags),
+            BuildKind::Go { goflags, .. } => Some(goflags),
+            BuildKind::Python { pipflags, .. } => Some(pipflags),
+            BuildKind::None | BuildKind::Remote | BuildKind::Custom { .. } => None,
+        }
+    }
 }
 
 impl BuildRecipe {
@@ -215,14 +482,152 @@ pub struct CookRecipe {
 
 impl Recipe {
     pub fn new(file: &PathBuf) -> Result<Recipe, PackageError> {
+        let mut parent_files = Vec::new();
+        let mut table = Self::read_extended(file, &mut parent_files)?;
+        let constraints = Self::extract_constraints(&mut table)
+            .map_err(|e| PackageError::Parse(serde::de::Error::custom(e), Some(file.clone())))?;
+        let mut recipe: Recipe = toml::Value::Table(table)
+            .try_into()
+            .map_err(|err| PackageError::Parse(err, Some(file.clone())))?;
+        // the first entry is this file
+        parent_files.remove(0);
+        recipe.parent_files = parent_files;
+        recipe.constraints = constraints;
+        Ok(recipe)
+    }
+
+    /// Strip version constraints from dependency lists in the raw table, so they can be
+    /// deserialized as package names
+    fn extract_constraints(table: &mut toml::Table) -> Result<RecipeConstraints, String> {
+        fn strip(
+            table: Option<&mut toml::Value>,
+            key: &str,
+        ) -> Result<BTreeMap<String, VersionConstraint>, String> {
+            let mut constraints = BTreeMap::new();
+            let Some(deps) = table
+                .and_then(|t| t.get_mut(key))
+                .and_then(|d| match d {
+                    // the replace form of target and variant lists
+                    toml::Value::Table(t) => t.get_mut("replace"),
+                    d => Some(d),
+                })
+                .and_then(|d| d.as_array_mut())
+            else {
+                return Ok(constraints);
+            };
+            for dep in deps.iter_mut() {
+                let Some(dep_str) = dep.as_str() else {
+                    continue;
+                };
+                let (name, constraint) = VersionConstraint::split_dependency(dep_str)?;
+                if let Some(constraint) = constraint {
+                    // "host:openssl3" is built from the same recipe as "openssl3"
+                    let key = PackageName::new(name)
+                        .map(|n| n.name().to_string())
+                        .unwrap_or_else(|_| name.to_string());
+                    constraints.insert(key, constraint);
+                    *dep = toml::Value::String(name.to_string());
+                }
+            }
+            Ok(constraints)
+        }
+
+        fn strip_lists(table: &mut toml::Table) -> Result<RecipeConstraints, String> {
+            Ok(RecipeConstraints {
+                build: strip(table.get_mut("build"), "dependencies")?,
+                dev: strip(table.get_mut("build"), "dev-dependencies")?,
+                package: strip(table.get_mut("package"), "dependencies")?,
+                ..Default::default()
+            })
+        }
+
+        let mut constraints = strip_lists(table)?;
+        if let Some(packages) = table
+            .get_mut("optional-packages")
+            .and_then(|p| p.as_array_mut())
+        {
+            for package in packages {
+                let name = package
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let deps = strip(Some(package), "dependencies")?;
+                if !deps.is_empty() {
+                    constraints.optional.insert(name, deps);
+                }
+            }
+        }
+        for (key, sections) in [
+            ("target", &mut constraints.target),
+            ("variants", &mut constraints.variants),
+        ] {
+            let Some(tables) = table.get_mut(key).and_then(|t| t.as_table_mut()) else {
+                continue;
+            };
+            for (name, section) in tables.iter_mut() {
+                if let Some(section) = section.as_table_mut() {
+                    let section = strip_lists(section)?;
+                    if section != RecipeConstraints::default() {
+                        sections.insert(name.clone(), section);
+                    }
+                }
+            }
+        }
+        Ok(constraints)
+    }
+
+    /// Read recipe toml as raw table, with its `extends` chain merged in.
+    /// `visited` is filled with the file and its parents, used to detect cycles.
+    fn read_extended(
+        file: &PathBuf,
+        visited: &mut Vec<PathBuf>,
+    ) -> Result<toml::Table, PackageError> {
         if !file.is_file() {
             return Err(PackageError::FileMissing(file.clone()));
         }
+        let canon_file = file
+            .canonicalize()
+            .map_err(|err| PackageError::FileError(err.raw_os_error(), file.clone()))?;
+        if visited.contains(&canon_file) {
+            // reported like a dependency loop, innermost recipe first
+            let mut err = PackageError::Recursion(Default::default());
+            for recipe_file in visited.iter().chain(std::iter::once(&canon_file)).rev() {
+                if let Some(name) = recipe_file
+                    .parent()
+                    .and_then(|d| d.file_name())
+                    .and_then(|n| PackageName::new(n.to_string_lossy()).ok())
+                {
+                    err.append_recursion(&name);
+                }
+            }
+            return Err(err);
+        }
+        visited.push(canon_file);
+
         let toml = fs::read_to_string(file)
             .map_err(|err| PackageError::FileError(err.raw_os_error(), file.clone()))?;
-        let recipe: Recipe =
+        let table: toml::Table =
             toml::from_str(&toml).map_err(|err| PackageError::Parse(err, Some(file.clone())))?;
-        Ok(recipe)
+        let Some(extends) = table.get("extends") else {
+            return Ok(table);
+        };
+        let extends: String = extends
+            .clone()
+            .try_into()
+            .map_err(|err| PackageError::Parse(err, Some(file.clone())))?;
+
+        let mut parent_file = file.parent().unwrap_or(Path::new(".")).join(&extends);
+        let mut parent_dir = Path::new(&extends);
+        if parent_file.is_dir() {
+            parent_file = parent_file.join("recipe.toml");
+        } else {
+            parent_dir = parent_dir.parent().unwrap_or(Path::new(""));
+        }
+        let mut parent = Self::read_extended(&parent_file, visited)?;
+        rebase_recipe_paths(&mut parent, parent_dir);
+        merge_recipe_table(&mut parent, table);
+        Ok(parent)
     }
 
     pub fn get_packages_list(&self) -> Vec<Option<&OptionalPackageRecipe>> {
@@ -235,8 +640,45 @@ impl Recipe {
 }
 
 impl CookRecipe {
-    pub fn new(name: PackageName, dir: PathBuf, mut recipe: Recipe) -> Result<Self, PackageError> {
+    pub fn new(
+        name: PackageName,
+        dir: PathBuf,
+        mut recipe: Recipe,
+        variants: &VariantSelection,
+    ) -> Result<Self, PackageError> {
         let target = cook_package::package_target(&name);
+        if let Some(target_recipe) = recipe.target.remove(target) {
+            Self::apply_target(&mut recipe, target_recipe)
+                .map_err(|e| PackageError::Parse(e, Some(dir.join("recipe.toml"))))?;
+        }
+        if let Some(constraints) = recipe.constraints.target.remove(target) {
+            recipe.constraints.extend(constraints);
+        }
+        Self::apply_variants(&name, &mut recipe, variants)
+            .map_err(|e| PackageError::Parse(e, Some(dir.join("recipe.toml"))))?;
+        // dependencies may only be provided virtually by another recipe
+        recipe.constraints.resolve_providers();
+        for deps in [
+            &mut recipe.build.dependencies,
+            &mut recipe.build.dev_dependencies,
+            &mut recipe.package.dependencies,
+        ]
+        .into_iter()
+        .chain(
+            recipe
+                .optional_packages
+                .iter_mut()
+                .map(|p| &mut p.dependencies),
+        ) {
+            for dep in deps.iter_mut() {
+                // empty name refers to optional packages of this recipe
+                if !dep.name().is_empty()
+                    && let Ok((resolved, _)) = staged_pkg::resolve(dep)
+                {
+                    *dep = resolved;
+                }
+            }
+        }
         if name.is_host() {
             let thisname = name.without_host();
             let fn_map = |p: PackageName| {
@@ -272,6 +714,107 @@ impl CookRecipe {
         })
     }
 
+    fn apply_target(recipe: &mut Recipe, target: TargetRecipe) -> Result<(), toml::de::Error> {
+        let TargetRecipe {
+            build,
+            package,
+            patches,
+        } = target;
+        build.dependencies.apply(&mut recipe.build.dependencies);
+        build
+            .dev_dependencies
+            .apply(&mut recipe.build.dev_dependencies);
+        recipe.build.env.extend(build.env);
+        package.dependencies.apply(&mut recipe.package.dependencies);
+        if !build.flags.is_noop() {
+            let Some(flags) = recipe.build.kind.flags_mut() else {
+                return Err(serde::de::Error::custom(
+                    "additional build flags are not supported by this template",
+                ));
+            };
+            build.flags.apply(flags);
+        }
+        if !patches.is_empty() {
+            let Some(source_patches) = recipe.source.as_mut().and_then(|s| s.patches_mut()) else {
+                return Err(serde::de::Error::custom(
+                    "additional patches are not supported by this source",
+                ));
+            };
+            source_patches.extend(patches);
+        }
+        Ok(())
+    }
+
+    /// Apply default and selected variants, exporting them as `COOKBOOK_VARIANTS`
+    fn apply_variants(
+        name: &PackageName,
+        recipe: &mut Recipe,
+        variants: &VariantSelection,
+    ) -> Result<(), toml::de::Error> {
+        let selected = variants
+            .get(name.name())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if recipe.variants.is_empty() {
+            if let Some(selected) = selected.first() {
+                return Err(serde::de::Error::custom(format!(
+                    "variant {:?} selected but this recipe has no variants",
+                    selected
+                )));
+            }
+            return Ok(());
+        }
+        let mut enabled: BTreeSet<String> = recipe
+            .variants
+            .iter()
+            .filter(|(_, v)| v.default)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for selected in selected {
+            let (variant, enable) = match selected.strip_prefix('!') {
+                Some(variant) => (variant, false),
+                None => (selected.as_str(), true),
+            };
+            if !recipe.variants.contains_key(variant) {
+                return Err(serde::de::Error::custom(format!(
+                    "unknown variant {variant:?}, expecting one of {:?}",
+                    recipe.variants.keys().collect::<Vec<_>>()
+                )));
+            }
+            if enable {
+                enabled.insert(variant.to_string());
+            } else {
+                enabled.remove(variant);
+            }
+        }
+        for variant in &enabled {
+            if let Some(constraints) = recipe.constraints.variants.get(variant).cloned() {
+                recipe.constraints.extend(constraints);
+            }
+            let VariantRecipe {
+                build,
+                package,
+                patches,
+                ..
+            } = recipe.variants[variant].clone();
+            Self::apply_target(
+                recipe,
+                TargetRecipe {
+                    build,
+                    package,
+                    patches,
+                },
+            )?;
+        }
+        recipe.enabled_variants = enabled.into_iter().collect();
+        // always exported, so toggling variants is detected as a build env change
+        recipe.build.env.insert(
+            "COOKBOOK_VARIANTS".to_string(),
+            recipe.enabled_variants.join(" "),
+        );
+        Ok(())
+    }
+
     pub fn dummy(name: &PackageName) -> Self {
         Self {
             dir: PathBuf::new(),
@@ -284,22 +827,25 @@ impl CookRecipe {
         }
     }
 
-    pub fn from_name(name: PackageName) -> Result<Self, PackageError> {
-        let dir = staged_pkg::find(name.name())
-            .ok_or_else(|| PackageError::PackageNotFound(name.clone()))?;
+    pub fn from_name(name: PackageName, variants: &VariantSelection) -> Result<Self, PackageError> {
+        let (name, dir) = staged_pkg::resolve(&name)?;
         let file = dir.join("recipe.toml");
         let recipe = Recipe::new(&file)?;
-        Self::new(name, dir.to_path_buf(), recipe)
+        Self::new(name, dir.to_path_buf(), recipe, variants)
     }
 
-    pub fn from_list(names: Vec<PackageName>) -> Result<Vec<Self>, PackageError> {
+    pub fn from_list(
+        names: Vec<PackageName>,
+        variants: &VariantSelection,
+    ) -> Result<Vec<Self>, PackageError> {
         let mut packages = Vec::new();
         for name in names {
-            packages.push(Self::from_name(name)?);
+            packages.push(Self::from_name(name, variants)?);
         }
         Ok(packages)
     }
 
+    /// Load the recipe in `dir` with only its default variants
     pub fn from_path(dir: &Path, read_recipe: bool, is_host: bool) -> Result<Self, PackageError> {
         let file = dir.join("recipe.toml");
         let mut name: PackageName = dir.file_name().unwrap().try_into()?;
@@ -312,7 +858,7 @@ impl CookRecipe {
             // clean/unfetch don't need to read recipe
             Recipe::default()
         };
-        Self::new(name, dir.to_path_buf(), recipe)
+        Self::new(name, dir.to_path_buf(), recipe, &VariantSelection::new())
     }
 
     fn new_recursive(
@@ -323,6 +869,7 @@ impl CookRecipe {
         collect_build_deps: bool,
         collect_package_deps: bool,
         collect_self: bool,
+        variants: &VariantSelection,
         recursion: usize,
     ) -> Result<Vec<Self>, PackageError> {
         if recursion == 0 {
@@ -332,7 +879,7 @@ impl CookRecipe {
         let mut recipes = Vec::new();
         let mut recipes_set = BTreeSet::new();
         for name in names {
-            let recipe = Self::from_name(name.clone())?;
+            let recipe = Self::from_name(name.clone(), variants)?;
 
             if recurse_build_deps {
                 let dependencies = Self::new_recursive(
@@ -343,6 +890,7 @@ impl CookRecipe {
                     collect_build_deps,
                     collect_package_deps,
                     collect_build_deps,
+                    variants,
                     recursion - 1,
                 )
                 .map_err(|mut err| {
@@ -367,6 +915,7 @@ impl CookRecipe {
                     collect_build_deps,
                     collect_package_deps,
                     collect_build_deps,
+                    variants,
                     recursion - 1,
                 )
                 .map_err(|mut err| {
@@ -391,6 +940,7 @@ impl CookRecipe {
                     collect_build_deps,
                     collect_package_deps,
                     collect_package_deps,
+                    variants,
                     recursion - 1,
                 )
                 .map_err(|mut err| {
@@ -418,6 +968,7 @@ impl CookRecipe {
     pub fn get_build_deps_recursive(
         names: &[PackageName],
         include_dev: bool,
+        variants: &VariantSelection,
     ) -> Result<Vec<Self>, PackageError> {
         let packages = Self::new_recursive(
             names,
@@ -427,6 +978,7 @@ impl CookRecipe {
             true,
             false,
             true,
+            variants,
             WALK_DEPTH,
         )?;
 
@@ -436,6 +988,7 @@ impl CookRecipe {
     pub fn get_package_deps_recursive(
         names: &[PackageName],
         include_names: bool,
+        variants: &VariantSelection,
     ) -> Result<Vec<PackageName>, PackageError> {
         // recurse_build_deps == true here as libraries (build deps) can have runtime files (package deps)
         let packages = Self::new_recursive(
@@ -446,6 +999,7 @@ impl CookRecipe {
             false,
             true,
             include_names,
+            variants,
             WALK_DEPTH,
         )?;
 
@@ -455,9 +1009,19 @@ impl CookRecipe {
     pub fn get_all_deps_names_recursive(
         names: &[PackageName],
         include_dev: bool,
+        variants: &VariantSelection,
     ) -> Result<Vec<PackageName>, PackageError> {
-        let packages =
-            Self::new_recursive(names, true, include_dev, true, true, true, true, WALK_DEPTH)?;
+        let packages = Self::new_recursive(
+            names,
+            true,
+            include_dev,
+            true,
+            true,
+            true,
+            true,
+            variants,
+            WALK_DEPTH,
+        )?;
 
         Ok(packages.into_iter().map(|p| p.name).collect())
     }
@@ -468,11 +1032,28 @@ impl CookRecipe {
             // TODO: print?
             return Ok(());
         }
-        self.recipe = Self::from_path(&self.dir, true, self.name.is_host())?.recipe;
+        // variants are selected in the rule
+        let variants = split_rule(&self.rule).1.into_iter().map(String::from);
+        let variants = VariantSelection::from([(self.name.name().to_string(), variants.collect())]);
+        let recipe = Recipe::new(&self.dir.join("recipe.toml"))?;
+        self.recipe = Self::new(self.name.clone(), self.dir.clone(), recipe, &variants)?.recipe;
         let _ = self.apply_filesystem_config(&self.rule.clone());
         Ok(())
     }
 
+    /// Description of this package, or of the optional package if the name has a suffix
+    pub fn description(&self) -> Option<&str> {
+        match self.name.suffix() {
+            Some(suffix) => self
+                .recipe
+                .optional_packages
+                .iter()
+                .find(|p| p.name == suffix)
+                .and_then(|p| p.description.as_deref()),
+            None => self.recipe.package.description.as_deref(),
+        }
+    }
+
     /// returns stage dir, pkgar file and toml file.
     pub fn stage_paths(&self) -> (PathBuf, PathBuf, PathBuf) {
         let r = self.name.suffix().map(|p| OptionalPackageRecipe {
@@ -487,7 +1068,15 @@ impl CookRecipe {
         self.dir.join("target").join(self.target)
     }
 
-    pub fn apply_filesystem_config(&mut self, rule: &str) -> crate::Result<()> {
+    pub fn apply_filesystem_config(&mut self, full_rule: &str) -> crate::Result<()> {
+        let (rule, variants) = split_rule(full_rule);
+        if !variants.is_empty() && !matches!(rule, "source" | "local") {
+            bail_other_err!(
+                "Invalid pkg config {} = {:?}\nVariants can only be selected with 'source' or 'local'",
+                self.name.as_str(),
+                rule
+            );
+        }
         match rule {
             // build from source as usual
             "source" => {}
@@ -513,7 +1102,7 @@ impl CookRecipe {
                 );
             }
         }
-        self.rule = rule.to_string();
+        self.rule = full_rule.to_string();
 
         Ok(())
     }
@@ -533,8 +1122,11 @@ impl CookRecipe {
                 SourceRecipe::Tar {
                     tar,
                     blake3: _,
+                    sha256: _,
+                    sha512: _,
                     patches: _,
                     script: _,
+                    extra: _,
                 } => {
                     if let Some(ver) = re.extract_ver(tar) {
                         return Some(ver);
@@ -544,11 +1136,16 @@ impl CookRecipe {
                     git: _,
                     upstream: _,
                     branch,
+                    tag,
                     rev,
                     shallow_clone: _,
                     patches: _,
                     script: _,
+                    extra: _,
                 } => {
+                    if let Some(tag) = tag {
+                        return Some(re.extract_ver(tag).unwrap_or(tag.clone()));
+                    }
                     if let Some(rev) = rev
                         && let Some(ver) = re.extract_ver(rev)
                     {
@@ -596,6 +1193,348 @@ impl CookRecipe {
     }
 }
 
+/// Make relative paths in a parent recipe table relative to the extending recipe,
+/// where `parent_dir` is the parent recipe dir relative to the extending recipe dir.
+/// Path sources are not rebased, as they are relative to the working directory.
+fn rebase_recipe_paths(table: &mut toml::Table, parent_dir: &Path) {
+    fn rebase(value: Option<&mut toml::Value>, parent_dir: &Path) {
+        match value {
+            Some(toml::Value::String(path)) => {
+                *path = parent_dir.join(&*path).to_string_lossy().to_string();
+            }
+            Some(toml::Value::Array(paths)) => {
+                for path in paths {
+                    rebase(Some(path), parent_dir);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if parent_dir.as_os_str().is_empty() {
+        return;
+    }
+    if let Some(source) = table.get_mut("source") {
+        rebase(source.get_mut("same_as"), parent_dir);
+        rebase(source.get_mut("patches"), parent_dir);
+    }
+    for key in ["target", "variants"] {
+        if let Some(tables) = table.get_mut(key).and_then(|t| t.as_table_mut()) {
+            for value in tables.values_mut() {
+                rebase(value.get_mut("patches"), parent_dir);
+            }
+        }
+    }
+}
+
+/// Deep-merge child recipe onto parent recipe. Tables are merged recursively,
+/// optional packages are merged by name, other values are replaced by the child.
+fn merge_recipe_table(parent: &mut toml::Table, child: toml::Table) {
+    for (key, value) in child {
+        match (key.as_str(), parent.get_mut(&key), value) {
+            // switching source kind should not keep keys of the parent kind
+            ("source", Some(toml::Value::Table(_)), toml::Value::Table(child_source))
+                if ["git", "tar", "path", "same_as"]
+                    .iter()
+                    .any(|k| child_source.contains_key(*k)) =>
+            {
+                parent.insert(key, toml::Value::Table(child_source));
+            }
+            (
+                "optional-packages",
+                Some(toml::Value::Array(parent_packages)),
+                toml::Value::Array(child_packages),
+            ) => {
+                for child_package in child_packages {
+                    let name = child_package.get("name").cloned();
+                    let parent_package = parent_packages
+                        .iter_mut()
+                        .find(|p| name.is_some() && p.get("name") == name.as_ref());
+                    match (parent_package, child_package) {
+                        (Some(toml::Value::Table(p)), toml::Value::Table(c)) => {
+                            merge_toml_table(p, c)
+                        }
+                        (_, c) => parent_packages.push(c),
+                    }
+                }
+            }
+            (_, Some(toml::Value::Table(parent_table)), toml::Value::Table(child_table)) => {
+                merge_toml_table(parent_table, child_table);
+            }
+            (_, _, value) => {
+                parent.insert(key, value);
+            }
+        }
+    }
+}
+
+fn merge_toml_table(parent: &mut toml::Table, child: toml::Table) {
+    for (key, value) in child {
+        match (parent.get_mut(&key), value) {
+            (Some(toml::Value::Table(parent_table)), toml::Value::Table(child_table)) => {
+                merge_toml_table(parent_table, child_table);
+            }
+            (_, value) => {
+                parent.insert(key, value);
+            }
+        }
+    }
+}
+
+impl CookRecipe {
+    /// Check version constraints of recipes and their dependencies against `guess_version`,
+    /// reporting the dependency chain of the first violation.
+    /// Dependencies with unknown version are not checked.
+    pub fn check_constraints(
+        recipes: &[CookRecipe],
+        variants: &VariantSelection,
+    ) -> Result<(), crate::Error> {
+        let mut loaded: BTreeMap<PackageName, CookRecipe> = recipes
+            .iter()
+            .map(|r| (r.name.clone(), r.clone()))
+            .collect();
+        let mut visited = BTreeSet::new();
+        let mut chain = Vec::new();
+        for recipe in recipes {
+            Self::check_constraints_recursive(
+                &recipe.name,
+                &mut loaded,
+                &mut visited,
+                &mut chain,
+                variants,
+                WALK_DEPTH,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check_constraints_recursive(
+        name: &PackageName,
+        loaded: &mut BTreeMap<PackageName, CookRecipe>,
+        visited: &mut BTreeSet<PackageName>,
+        chain: &mut Vec<PackageName>,
+        variants: &VariantSelection,
+        recursion: usize,
+    ) -> Result<(), crate::Error> {
+        if recursion == 0 || !visited.insert(name.clone()) {
+            return Ok(());
+        }
+        let recipe = match loaded.get(name) {
+            Some(recipe) => recipe.clone(),
+            None => {
+                let recipe = Self::from_name(name.clone(), variants)?;
+                loaded.insert(name.clone(), recipe.clone());
+                recipe
+            }
+        };
+        chain.push(name.clone());
+        let constraints = &recipe.recipe.constraints;
+        let no_constraints = BTreeMap::new();
+        let optional = recipe.recipe.optional_packages.iter().map(|p| {
+            let optional = constraints.optional.get(&p.name);
+            (&p.dependencies, optional.unwrap_or(&no_constraints))
+        });
+        let lists = [
+            (&recipe.recipe.build.dependencies, &constraints.build),
+            (&recipe.recipe.build.dev_dependencies, &constraints.dev),
+            (&recipe.recipe.package.dependencies, &constraints.package),
+        ]
+        .into_iter()
+        .chain(optional);
+        for (deps, constraints) in lists {
+            for dep in deps {
+                if dep.name().is_empty() {
+                    continue;
+                }
+                if let Some(constraint) = constraints.get(dep.name()) {
+                    let dep_recipe = match loaded.get(dep) {
+                        Some(dep_recipe) => dep_recipe.clone(),
+                        None => Self::from_name(dep.clone(), variants)?,
+                    };
+                    if let Some(version) = dep_recipe.guess_version()
+                        && !constraint.matches(&version)
+                    {
+                        return Err(crate::Error::Constraint {
+                            name: dep.clone(),
+                            version,
+                            constraint: constraint.clone(),
+                            chain: chain.clone(),
+                        });
+                    }
+                }
+                Self::check_constraints_recursive(
+                    dep,
+                    loaded,
+                    visited,
+                    chain,
+                    variants,
+                    recursion - 1,
+                )?;
+            }
+        }
+        chain.pop();
+        Ok(())
+    }
+}
+
+/// Variants selected from the filesystem config, keyed by recipe name
+pub type VariantSelection = BTreeMap<String, Vec<String>>;
+
+/// Split a filesystem config rule such as "source+x11+!orbital" into
+/// the rule and selected variants, where "!" disables a default variant
+pub fn split_rule(rule: &str) -> (&str, Vec<&str>) {
+    let mut parts = rule.split('+');
+    let rule = parts.next().unwrap_or_default();
+    (rule, parts.filter(|p| !p.is_empty()).collect())
+}
+
+impl RecipeConstraints {
+    /// Add constraints of a target or variant section, replacing existing ones
+    fn extend(&mut self, other: RecipeConstraints) {
+        self.build.extend(other.build);
+        self.dev.extend(other.dev);
+        self.package.extend(other.package);
+    }
+
+    /// Key constraints by the recipe providing each dependency
+    fn resolve_providers(&mut self) {
+        let resolve = |map: &mut BTreeMap<String, VersionConstraint>| {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(dep, constraint)| {
+                    let resolved = PackageName::new(&dep)
+                        .ok()
+                        .and_then(|name| staged_pkg::resolve(&name).ok())
+                        .map(|(name, _)| name.name().to_string());
+                    (resolved.unwrap_or(dep), constraint)
+                })
+                .collect();
+        };
+        resolve(&mut self.build);
+        resolve(&mut self.dev);
+        resolve(&mut self.package);
+        self.optional.values_mut().for_each(resolve);
+    }
+}
+
+/// A version requirement of a dependency, such as ">= 3.0, < 4"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConstraint {
+    comparators: Vec<(String, String)>,
+}
+
+impl VersionConstraint {
+    /// Split a dependency such as "openssl3 >= 3.0" into its name and constraint
+    pub fn split_dependency(dep: &str) -> Result<(&str, Option<Self>), String> {
+        let dep = dep.trim();
+        match dep.find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '=' | '!')) {
+            Some(i) => Ok((dep[..i].trim_end(), Some(dep[i..].parse()?))),
+            None => Ok((dep, None)),
+        }
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        self.comparators.iter().all(|(op, expected)| {
+            let ord = compare_versions(version, expected);
+            match op.as_str() {
+                ">=" => ord != Ordering::Less,
+                "<=" => ord != Ordering::Greater,
+                ">" => ord == Ordering::Greater,
+                "<" => ord == Ordering::Less,
+                "!=" => ord != Ordering::Equal,
+                _ => ord == Ordering::Equal,
+            }
+        })
+    }
+}
+
+impl FromStr for VersionConstraint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut comparators = Vec::new();
+        for part in s.split(',') {
+            let part = part.trim();
+            let Some((op, version)) = [">=", "<=", "==", "!=", ">", "<", "="]
+                .iter()
+                .find_map(|op| part.strip_prefix(op).map(|v| (*op, v.trim())))
+            else {
+                return Err(format!("invalid version constraint {part:?}"));
+            };
+            if version.is_empty() || version.contains(char::is_whitespace) {
+                return Err(format!("invalid version constraint {part:?}"));
+            }
+            let op = if op == "==" { "=" } else { op };
+            comparators.push((op.to_string(), version.to_string()));
+        }
+        Ok(Self { comparators })
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .comparators
+            .iter()
+            .map(|(op, version)| format!("{op} {version}"))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// A part of a version, ordered so that pre-releases come before the release
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum VersionPart<'a> {
+    PreRelease(&'a str),
+    Number(u64),
+    Other(&'a str),
+}
+
+impl<'a> VersionPart<'a> {
+    /// Split "3.0-rc1" into 3, 0, "rc" and 1
+    fn split(version: &'a str) -> Vec<Self> {
+        let mut parts = Vec::new();
+        for segment in version.trim_start_matches('v').split(['.', '-', '+', '_']) {
+            let mut rest = segment;
+            while let Some(first) = rest.chars().next() {
+                let digit = first.is_ascii_digit();
+                let end = rest
+                    .find(|c: char| c.is_ascii_digit() != digit)
+                    .unwrap_or(rest.len());
+                let (part, tail) = rest.split_at(end);
+                parts.push(if let Ok(number) = part.parse() {
+                    VersionPart::Number(number)
+                } else if ["alpha", "beta", "dev", "pre", "rc"]
+                    .iter()
+                    .any(|pre| part.eq_ignore_ascii_case(pre))
+                {
+                    VersionPart::PreRelease(part)
+                } else {
+                    VersionPart::Other(part)
+                });
+                rest = tail;
+            }
+        }
+        parts
+    }
+}
+
+/// Compare versions part by part, numerically if both parts are numbers.
+/// Missing parts count as zero, so "3.0" is equal to "3", and "3.0-rc1" is before "3.0".
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let a = VersionPart::split(a);
+    let b = VersionPart::split(b);
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).unwrap_or(&VersionPart::Number(0));
+        let y = b.get(i).unwrap_or(&VersionPart::Number(0));
+        let ord = x.cmp(y);
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
 // TODO: Wrap these vectors in a struct
 
 pub fn recipes_mark_as_deps(names: &[PackageName], packages: &mut Vec<CookRecipe>) {
@@ -702,10 +1641,12 @@ mod tests {
                     git: "https://gitlab.redox-os.org/redox-os/acid.git".to_string(),
                     upstream: None,
                     branch: Some("master".to_string()),
+                    tag: None,
                     rev: Some("06344744d3d55a5ac9a62a6059cb363d40699bbc".to_string()),
                     patches: Vec::new(),
                     script: None,
                     shallow_clone: None,
+                    extra: Vec::new(),
                 }),
                 build: BuildRecipe::new(BuildKind::Cargo {
                     cargopath: None,
@@ -746,8 +1687,11 @@ mod tests {
                         "8220c0e4082fa26c07b10bfe31f641d2e33ebe1d1bb0b20221b7016bc8b78a3a"
                             .to_string()
                     ),
+                    sha256: None,
+                    sha512: None,
                     patches: Vec::new(),
                     script: None,
+                    extra: Vec::new(),
                 }),
                 build: BuildRecipe::new(BuildKind::Custom {
                     script: "make".to_string()
@@ -757,6 +1701,435 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extends_recipe() {
+        use crate::recipe::{BuildKind, Recipe, SourceRecipe, merge_recipe_table};
+
+        let mut parent: toml::Table = toml::from_str(
+            r#"
+            [source]
+            tar = "https://example.com/libfoo-1.0.tar.xz"
+            blake3 = "0000"
+
+            [build]
+            template = "configure"
+            configureflags = ["--disable-shared"]
+            dependencies = ["zlib"]
+
+            [[optional-packages]]
+            name = "dev"
+            files = ["/usr/include"]
+        "#,
+        )
+        .unwrap();
+        let child: toml::Table = toml::from_str(
+            r#"
+            extends = "../libfoo"
+
+            [source]
+            git = "https://example.com/libbar.git"
+
+            [build]
+            configureflags = ["--enable-bar"]
+
+            [[optional-packages]]
+            name = "dev"
+            dependencies = ["zlib"]
+        "#,
+        )
+        .unwrap();
+        merge_recipe_table(&mut parent, child);
+        let recipe: Recipe = toml::Value::Table(parent).try_into().unwrap();
+
+        assert_eq!(recipe.extends, Some("../libfoo".to_string()));
+        assert!(matches!(
+            recipe.source,
+            Some(SourceRecipe::Git { git, .. }) if git == "https://example.com/libbar.git"
+        ));
+        assert_eq!(
+            recipe.build.kind,
+            BuildKind::Configure {
+                configureflags: vec!["--enable-bar".to_string()]
+            }
+        );
+        assert_eq!(
+            recipe.build.dependencies,
+            vec![PackageName::new("zlib").unwrap()]
+        );
+        assert_eq!(recipe.optional_packages.len(), 1);
+        assert_eq!(recipe.optional_packages[0].files, vec!["/usr/include"]);
+        assert_eq!(
+            recipe.optional_packages[0].dependencies,
+            vec![PackageName::new("zlib").unwrap()]
+        );
+    }
+
+    #[test]
+    fn extends_recipe_files() {
+        use crate::recipe::{Recipe, SourceRecipe};
+        use pkg::PackageError;
+
+        let root = std::env::temp_dir().join("temp_test_dir_extends_recipe_files");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("libs/libfoo")).unwrap();
+        std::fs::create_dir_all(root.join("other/libbar")).unwrap();
+        std::fs::write(
+            root.join("libs/libfoo/recipe.toml"),
+            r#"
+            [source]
+            tar = "https://example.com/libfoo-1.0.tar.xz"
+            patches = ["fix.patch"]
+
+            [build]
+            template = "configure"
+
+            [target.x86_64-unknown-redox]
+            patches = ["x86_64.patch"]
+        "#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("other/libbar/recipe.toml"),
+            r#"extends = "../../libs/libfoo""#,
+        )
+        .unwrap();
+
+        // inherited paths are relative to the parent recipe dir
+        let recipe = Recipe::new(&root.join("other/libbar/recipe.toml")).unwrap();
+        let Some(SourceRecipe::Tar { patches, .. }) = &recipe.source else {
+            panic!("expected tar source");
+        };
+        assert_eq!(patches, &vec!["../../libs/libfoo/fix.patch".to_string()]);
+        assert_eq!(
+            recipe.target["x86_64-unknown-redox"].patches,
+            vec!["../../libs/libfoo/x86_64.patch".to_string()]
+        );
+
+        std::fs::write(
+            root.join("libs/libfoo/recipe.toml"),
+            r#"extends = "../../other/libbar""#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Recipe::new(&root.join("other/libbar/recipe.toml")),
+            Err(PackageError::Recursion(_))
+        ));
+    }
+
+    #[test]
+    fn make_recipe() {
+        use crate::recipe::{BuildKind, BuildRecipe, Recipe};
+
+        let recipe: Recipe = toml::from_str(
+            r#"
+            [build]
+            template = "make"
+            makepath = "src"
+            makeflags = ["NO_X11=1"]
+            install_target = "install-bin"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recipe,
+            Recipe {
+                build: BuildRecipe::new(BuildKind::Make {
+                    makepath: Some("src".to_string()),
+                    makeflags: vec!["NO_X11=1".to_string()],
+                    install_target: Some("install-bin".to_string()),
+                }),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn cargo_c_recipe() {
+        use crate::recipe::{BuildKind, BuildRecipe, Recipe};
+
+        let recipe: Recipe = toml::from_str(
+            r#"
+            [build]
+            template = "cargo-c"
+            cargopath = "capi"
+            librarytype = "cdylib"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recipe,
+            Recipe {
+                build: BuildRecipe::new(BuildKind::CargoC {
+                    cargopath: Some("capi".to_string()),
+                    cargoflags: Vec::new(),
+                    librarytype: Some("cdylib".to_string()),
+                }),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn tar_extra_recipe() {
+        use crate::recipe::{ExtraSource, ExtraSourceRecipe, Recipe, SourceRecipe};
+
+        let recipe: Recipe = toml::from_str(
+            r#"
+            [source]
+            tar = "https://example.com/foo-1.0.tar.xz"
+
+            [[source.extra]]
+            dir = "testsuite"
+            tar = "https://example.com/foo-tests-1.0.tar.xz"
+            blake3 = "0000"
+
+            [[source.extra]]
+            dir = "third_party/bar"
+            git = "https://example.com/bar.git"
+            rev = "1111"
+        "#,
+        )
+        .unwrap();
+
+        let Some(SourceRecipe::Tar { extra, .. }) = recipe.source else {
+            panic!("expected tar source");
+        };
+        assert_eq!(
+            extra,
+            vec![
+                ExtraSourceRecipe {
+                    dir: "testsuite".to_string(),
+                    source: ExtraSource::Tar {
+                        tar: "https://example.com/foo-tests-1.0.tar.xz".to_string(),
+                        blake3: Some("0000".to_string()),
+                    },
+                },
+                ExtraSourceRecipe {
+                    dir: "third_party/bar".to_string(),
+                    source: ExtraSource::Git {
+                        git: "https://example.com/bar.git".to_string(),
+                        branch: None,
+                        rev: Some("1111".to_string()),
+                    },
+                },
+            ]
+        );
+        assert_eq!(extra[1].ident(), "third_party/bar:1111");
+    }
+
+    #[test]
+    fn git_tag_version() {
+        use crate::recipe::{CookRecipe, Recipe, SourceRecipe};
+
+        let recipe: Recipe = toml::from_str(
+            r#"
+            [source]
+            git = "https://example.com/foo.git"
+            tag = "v1.2.3"
+
+            [build]
+            template = "cargo"
+        "#,
+        )
+        .unwrap();
+
+        let mut recipe = CookRecipe::new(
+            PackageName::new("foo").unwrap(),
+            Default::default(),
+            recipe,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(recipe.guess_version(), Some("1.2.3".to_string()));
+
+        // rev locked by capture-rev should not change the version
+        if let Some(SourceRecipe::Git { rev, .. }) = &mut recipe.recipe.source {
+            *rev = Some("06344744d3d55a5ac9a62a6059cb363d40699bbc".to_string());
+        }
+        assert_eq!(recipe.guess_version(), Some("1.2.3".to_string()));
+    }
+
+    #[test]
+    fn target_recipe() {
+        use crate::recipe::{BuildKind, CookRecipe, Recipe, SourceRecipe};
+
+        let recipe: Recipe = toml::from_str(&format!(
+            r#"
+            [source]
+            tar = "https://example.com/foo-1.0.tar.xz"
+
+            [build]
+            template = "configure"
+            dependencies = ["zlib"]
+            configureflags = ["--disable-nls"]
+
+            [target.{target}]
+            build.dependencies = ["libgcc"]
+            build.flags = ["--disable-asm"]
+            patches = ["target.patch"]
+
+            [target.not-this-target]
+            build.dependencies = ["openssl3"]
+        "#,
+            target = redoxer::target()
+        ))
+        .unwrap();
+
+        let recipe = CookRecipe::new(
+            PackageName::new("foo").unwrap(),
+            Default::default(),
+            recipe,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            recipe.recipe.build.dependencies,
+            vec![
+                PackageName::new("zlib").unwrap(),
+                PackageName::new("libgcc").unwrap()
+            ]
+        );
+        assert_eq!(
+            recipe.recipe.build.kind,
+            BuildKind::Configure {
+                configureflags: vec!["--disable-nls".to_string(), "--disable-asm".to_string()]
+            }
+        );
+        let Some(SourceRecipe::Tar { patches, .. }) = recipe.recipe.source else {
+            panic!("expected tar source");
+        };
+        assert_eq!(patches, vec!["target.patch".to_string()]);
+    }
+
+    #[test]
+    fn target_recipe_replace() {
+        use crate::recipe::{BuildKind, CookRecipe, Recipe};
+
+        let recipe: Recipe = toml::from_str(&format!(
+            r#"
+            [build]
+            template = "configure"
+            dependencies = ["zlib", "libiconv"]
+            configureflags = ["--disable-nls", "--enable-asm"]
+
+            [package]
+            dependencies = ["ca-certificates"]
+
+            [target.{target}.build]
+            dependencies = {{ replace = ["zlib"] }}
+            flags = {{ replace = ["--disable-asm"] }}
+
+            [target.{target}.package]
+            dependencies = ["libgcc"]
+        "#,
+            target = redoxer::target()
+        ))
+        .unwrap();
+
+        let recipe = CookRecipe::new(
+            PackageName::new("foo").unwrap(),
+            Default::default(),
+            recipe,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            recipe.recipe.build.dependencies,
+            vec![PackageName::new("zlib").unwrap()]
+        );
+        assert_eq!(
+            recipe.recipe.build.kind,
+            BuildKind::Configure {
+                configureflags: vec!["--disable-asm".to_string()]
+            }
+        );
+        assert_eq!(
+            recipe.recipe.package.dependencies,
+            vec![
+                PackageName::new("ca-certificates").unwrap(),
+                PackageName::new("libgcc").unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_recipe() {
+        use crate::recipe::{BuildKind, CookRecipe, Recipe, VariantSelection, split_rule};
+
+        assert_eq!(split_rule("source"), ("source", vec![]));
+        assert_eq!(
+            split_rule("local+x11+!orbital"),
+            ("local", vec!["x11", "!orbital"])
+        );
+
+        let variants = VariantSelection::from([(
+            "bar".to_string(),
+            vec!["x11".to_string(), "!orbital".to_string()],
+        )]);
+        let recipe: Recipe = toml::from_str(
+            r#"
+            [build]
+            template = "configure"
+
+            [variants.orbital]
+            default = true
+            build.flags = ["--enable-orbital"]
+
+            [variants.x11]
+            build.dependencies = ["libx11"]
+            build.flags = ["--enable-x11"]
+        "#,
+        )
+        .unwrap();
+
+        let foo = CookRecipe::new(
+            PackageName::new("foo").unwrap(),
+            Default::default(),
+            recipe.clone(),
+            &variants,
+        )
+        .unwrap();
+        assert_eq!(foo.recipe.enabled_variants, vec!["orbital".to_string()]);
+        assert_eq!(
+            foo.recipe.build.kind,
+            BuildKind::Configure {
+                configureflags: vec!["--enable-orbital".to_string()]
+            }
+        );
+        assert_eq!(foo.recipe.build.env["COOKBOOK_VARIANTS"], "orbital");
+
+        let bar = CookRecipe::new(
+            PackageName::new("bar").unwrap(),
+            Default::default(),
+            recipe.clone(),
+            &variants,
+        )
+        .unwrap();
+        assert_eq!(bar.recipe.enabled_variants, vec!["x11".to_string()]);
+        assert_eq!(
+            bar.recipe.build.dependencies,
+            vec![PackageName::new("libx11").unwrap()]
+        );
+        assert_eq!(
+            bar.recipe.build.kind,
+            BuildKind::Configure {
+                configureflags: vec!["--enable-x11".to_string()]
+            }
+        );
+
+        // without the selection, the defaults apply again
+        let bar = CookRecipe::new(
+            PackageName::new("bar").unwrap(),
+            Default::default(),
+            recipe,
+            &VariantSelection::new(),
+        )
+        .unwrap();
+        assert_eq!(bar.recipe.enabled_variants, vec!["orbital".to_string()]);
+    }
+
     #[test]
     fn meta_recipe() {
         use crate::recipe::{BuildKind, BuildRecipe, PackageRecipe, Recipe};
@@ -784,4 +2157,70 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn version_constraints() {
+        use crate::recipe::VersionConstraint;
+
+        let (name, constraint) = VersionConstraint::split_dependency("openssl3 >= 3.0").unwrap();
+        assert_eq!(name, "openssl3");
+        let constraint = constraint.unwrap();
+        assert!(constraint.matches("3.0.13"));
+        assert!(constraint.matches("3"));
+        assert!(!constraint.matches("1.1.1w"));
+
+        let (name, constraint) = VersionConstraint::split_dependency("zlib>=1.2, <2").unwrap();
+        assert_eq!(name, "zlib");
+        let constraint = constraint.unwrap();
+        assert_eq!(constraint.to_string(), ">= 1.2, < 2");
+        assert!(constraint.matches("1.3.1"));
+        assert!(!constraint.matches("2.0"));
+        assert!(!constraint.matches("1.1"));
+
+        assert_eq!(
+            VersionConstraint::split_dependency("libpng").unwrap(),
+            ("libpng", None)
+        );
+        assert!(VersionConstraint::split_dependency("libpng ~ 1").is_err());
+
+        let constraint: VersionConstraint = ">= 3.0".parse().unwrap();
+        assert!(!constraint.matches("3.0-rc1"));
+        assert!(!constraint.matches("3.0rc2"));
+        assert!(constraint.matches("3.0.1"));
+        let constraint: VersionConstraint = "> 1.1.1".parse().unwrap();
+        assert!(constraint.matches("1.1.1w"));
+        assert!(!constraint.matches("1.1.1-beta2"));
+    }
+
+    #[test]
+    fn extract_constraints() {
+        use crate::recipe::Recipe;
+
+        let mut table: toml::Table = toml::from_str(
+            r#"
+            [build]
+            dependencies = ["host:openssl3 >= 3.0"]
+            dev-dependencies = ["zlib"]
+
+            [package]
+            dependencies = ["openssl3 < 4"]
+
+            [target.x86_64-unknown-redox.build]
+            dependencies = ["libpng >= 1.6"]
+        "#,
+        )
+        .unwrap();
+        let constraints = Recipe::extract_constraints(&mut table).unwrap();
+        assert_eq!(constraints.build["openssl3"].to_string(), ">= 3.0");
+        assert!(constraints.dev.is_empty());
+        assert_eq!(constraints.package["openssl3"].to_string(), "< 4");
+        assert_eq!(
+            constraints.target["x86_64-unknown-redox"].build["libpng"].to_string(),
+            ">= 1.6"
+        );
+        assert_eq!(
+            table["build"]["dependencies"],
+            toml::Value::Array(vec!["host:openssl3".into()])
+        );
+    }
 }
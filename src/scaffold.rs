@@ -0,0 +1,369 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use toml_edit::DocumentMut;
+
+use crate::cook::download::{Checksums, download};
+use crate::cook::fetch::fetch_extract_tar;
+use crate::cook::fs::{create_dir, get_file_blake3, git_output, run_command};
+use crate::cook::pty::PtyOut;
+use crate::format::format_recipe;
+use crate::recipe::{BuildKind, BuildRecipe, Recipe, SourceRecipe};
+use crate::{Error, Result, wrap_io_err};
+
+// This file contains the recipe scaffolding from a source tree, used by `repo new`.
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tar.zst", ".zip", ".7z",
+];
+
+/// Write a starter `recipe.toml` into `recipe_dir` for the source at `from`, which is either
+/// a git URL, a tar URL, or a local directory or tar file. Remote sources are fetched into
+/// the recipe dir, just like `repo fetch` would do, to inspect them. Local sources without
+/// a URL to fetch them from get a TODO comment instead of their path.
+pub fn new_recipe(recipe_dir: &Path, from: &str, logger: &PtyOut) -> Result<PathBuf> {
+    let recipe_file = recipe_dir.join("recipe.toml");
+    if recipe_file.exists() {
+        return Err(Error::Other(format!(
+            "{} already exists",
+            recipe_file.display()
+        )));
+    }
+    create_dir(recipe_dir)?;
+
+    let source_dir = recipe_dir.join("source");
+    let local_dir = Path::new(from);
+    let is_tar = ARCHIVE_EXTENSIONS.iter().any(|ext| from.ends_with(ext));
+    let local_tar = is_tar && local_dir.is_file();
+    let (source, inspect_dir) = if is_tar {
+        let source_tar = recipe_dir.join("source.tar");
+        if local_tar {
+            fs::copy(local_dir, &source_tar).map_err(wrap_io_err!(
+                local_dir,
+                source_tar,
+                "Copying tar"
+            ))?;
+        } else {
+            download(from, &source_tar, Checksums::NONE, logger)?;
+        }
+        create_dir(&source_dir)?;
+        fetch_extract_tar(source_tar.clone(), &source_dir, logger)?;
+        let tar = match local_dir.file_name() {
+            // only the file name, as a local path is no use to others
+            Some(name) if local_tar => name.to_string_lossy().into_owned(),
+            _ => from.to_string(),
+        };
+        let source = SourceRecipe::Tar {
+            tar,
+            blake3: Some(get_file_blake3(&source_tar)?),
+            sha256: None,
+            sha512: None,
+            patches: Vec::new(),
+            script: None,
+            extra: Vec::new(),
+        };
+        (Some(source), source_dir)
+    } else if local_dir.is_dir() {
+        // a local path is no use to others, only the remote the clone came from is
+        let origin = if local_dir.join(".git").exists() {
+            git_output(local_dir, &["remote", "get-url", "origin"]).ok()
+        } else {
+            None
+        };
+        let source = origin.map(|git| git_source(git, local_dir)).transpose()?;
+        (source, local_dir.to_path_buf())
+    } else {
+        let mut command = Command::new("git");
+        command.arg("clone").arg(from).arg(&source_dir);
+        run_command(command, logger)?;
+        (Some(git_source(from.to_string(), &source_dir)?), source_dir)
+    };
+    let missing_source = source.is_none();
+
+    let recipe = Recipe {
+        source,
+        build: BuildRecipe::new(detect_build(&inspect_dir)),
+        ..Default::default()
+    };
+    let mut recipe = toml::Value::try_from(&recipe)
+        .map_err(|e| Error::Other(format!("Serializing recipe: {e}")))?;
+    prune_empty(&mut recipe);
+    let content =
+        toml::to_string(&recipe).map_err(|e| Error::Other(format!("Serializing recipe: {e}")))?;
+    let mut content = format_recipe(&content)?;
+    if local_tar {
+        content = add_tar_todo(&content, from)?;
+    } else if missing_source {
+        content = format!("# TODO: add a [source] with the git or tar URL of {from}\n{content}");
+    }
+    fs::write(&recipe_file, content).map_err(wrap_io_err!(recipe_file, "Writing recipe"))?;
+    Ok(recipe_file)
+}
+
+/// A git source pinned to the current commit of `dir`
+fn git_source(git: String, dir: &Path) -> Result<SourceRecipe> {
+    Ok(SourceRecipe::Git {
+        git,
+        upstream: None,
+        branch: None,
+        tag: None,
+        rev: Some(git_output(dir, &["rev-parse", "HEAD"])?),
+        shallow_clone: None,
+        patches: Vec::new(),
+        script: None,
+        extra: Vec::new(),
+    })
+}
+
+/// Guess the build template from build system files in the source tree
+pub fn detect_build(dir: &Path) -> BuildKind {
+    if let Some(kind) = detect_cargo(dir) {
+        kind
+    } else if dir.join("meson.build").is_file() {
+        BuildKind::Meson {
+            mesonflags: Vec::new(),
+        }
+    } else if dir.join("CMakeLists.txt").is_file() {
+        BuildKind::Cmake {
+            cmakeflags: Vec::new(),
+        }
+    } else if dir.join("configure").is_file() || dir.join("configure.ac").is_file() {
+        BuildKind::Configure {
+            configureflags: Vec::new(),
+        }
+    } else if dir.join("pyproject.toml").is_file() || dir.join("setup.py").is_file() {
+        BuildKind::Python {
+            pyprojectpath: None,
+            pipflags: Vec::new(),
+            legacysetup: !dir.join("pyproject.toml").is_file(),
+        }
+    } else if ["Makefile", "makefile", "GNUmakefile"]
+        .iter()
+        .any(|f| dir.join(f).is_file())
+    {
+        BuildKind::Make {
+            makepath: None,
+            makeflags: Vec::new(),
+            install_target: None,
+        }
+    } else {
+        BuildKind::Custom {
+            script: "# TODO: build the source and install it into \"${COOKBOOK_STAGE}\"\n"
+                .to_string(),
+        }
+    }
+}
+
+fn detect_cargo(dir: &Path) -> Option<BuildKind> {
+    let manifest = read_manifest(dir)?;
+    if manifest
+        .get("package")
+        .and_then(|p| p.get("metadata"))
+        .and_then(|m| m.get("capi"))
+        .is_some()
+    {
+        return Some(BuildKind::CargoC {
+            cargopath: None,
+            cargoflags: Vec::new(),
+            librarytype: None,
+        });
+    }
+
+    let mut cargopackages = Vec::new();
+    let mut cargoexamples = Vec::new();
+    if manifest.contains_key("package") {
+        // a root package is installed as is, unless it only has examples
+        if !has_bins(dir, &manifest) {
+            cargoexamples = list_examples(dir, &manifest);
+        }
+    } else {
+        let members = manifest
+            .get("workspace")
+            .and_then(|w| w.get("members"))
+            .and_then(|m| m.as_array())
+            .cloned()
+            .unwrap_or_default();
+        for member in members.iter().filter_map(|m| m.as_str()) {
+            let member_dirs = match member.strip_suffix("/*") {
+                Some(parent) => fs::read_dir(dir.join(parent))
+                    .map(|entries| entries.filter_map(|e| Some(e.ok()?.path())).collect())
+                    .unwrap_or_default(),
+                None => vec![dir.join(member)],
+            };
+            for member_dir in member_dirs {
+                let Some(member_manifest) = read_manifest(&member_dir) else {
+                    continue;
+                };
+                if has_bins(&member_dir, &member_manifest)
+                    && let Some(name) = member_manifest
+                        .get("package")
+                        .and_then(|p| p.get("name"))
+                        .and_then(|n| n.as_str())
+                {
+                    cargopackages.push(name.to_string());
+                }
+            }
+        }
+        cargopackages.sort();
+    }
+
+    Some(BuildKind::Cargo {
+        cargopath: None,
+        cargoflags: Vec::new(),
+        cargopackages,
+        cargoexamples,
+        clearlocked: false,
+        cargopackagesprefixed: false,
+    })
+}
+
+fn read_manifest(dir: &Path) -> Option<toml::Table> {
+    fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()?
+        .parse()
+        .ok()
+}
+
+fn has_bins(dir: &Path, manifest: &toml::Table) -> bool {
+    dir.join("src/main.rs").is_file()
+        || dir.join("src/bin").is_dir()
+        || manifest
+            .get("bin")
+            .and_then(|b| b.as_array())
+            .is_some_and(|b| !b.is_empty())
+}
+
+fn list_examples(dir: &Path, manifest: &toml::Table) -> Vec<String> {
+    let mut examples: Vec<String> = manifest
+        .get("example")
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.get("name")?.as_str().map(String::from))
+        .collect();
+    if let Ok(entries) = fs::read_dir(dir.join("examples")) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "rs")
+                && let Some(stem) = path.file_stem()
+            {
+                examples.push(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+    examples.sort();
+    examples.dedup();
+    examples
+}
+
+/// Local archives have no URL to put in the recipe, leave a note to fill it in
+fn add_tar_todo(content: &str, from: &str) -> Result<String> {
+    let mut doc = content
+        .parse::<DocumentMut>()
+        .map_err(|e| Error::Other(format!("Parsing recipe: {e}")))?;
+    if let Some(mut key) = doc
+        .get_mut("source")
+        .and_then(|s| s.as_table_mut())
+        .and_then(|s| s.key_mut("tar"))
+    {
+        key.leaf_decor_mut()
+            .set_prefix(format!("# TODO: replace with the download URL of {from}\n"));
+    }
+    Ok(doc.to_string())
+}
+
+/// Remove empty arrays, empty tables and false values, which are the defaults
+fn prune_empty(value: &mut toml::Value) {
+    if let toml::Value::Table(table) = value {
+        table.values_mut().for_each(prune_empty);
+        table.retain(|_, v| match v {
+            toml::Value::Array(a) => !a.is_empty(),
+            toml::Value::Table(t) => !t.is_empty(),
+            toml::Value::Boolean(b) => *b,
+            _ => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::recipe::BuildKind;
+    use crate::scaffold::{add_tar_todo, detect_build, new_recipe};
+
+    #[test]
+    fn local_tar_todo() {
+        assert_eq!(
+            add_tar_todo(
+                "[source]\ntar = \"foo-1.0.tar.gz\"\nblake3 = \"abc\"\n",
+                "/tmp/foo-1.0.tar.gz"
+            )
+            .unwrap(),
+            "[source]\n\
+             # TODO: replace with the download URL of /tmp/foo-1.0.tar.gz\n\
+             tar = \"foo-1.0.tar.gz\"\n\
+             blake3 = \"abc\"\n"
+        );
+    }
+
+    #[test]
+    fn local_dir_todo() {
+        let root = std::env::temp_dir().join("temp_test_dir_local_dir_todo");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("foo")).unwrap();
+        fs::write(root.join("foo/Makefile"), "").unwrap();
+        let from = root.join("foo").to_string_lossy().into_owned();
+
+        let recipe_file = new_recipe(&root.join("recipe"), &from, &None).unwrap();
+        let content = fs::read_to_string(recipe_file).unwrap();
+        assert!(content.starts_with(&format!(
+            "# TODO: add a [source] with the git or tar URL of {from}\n"
+        )));
+        assert!(!content.contains("path ="));
+    }
+
+    #[test]
+    fn detect_cargo_workspace() {
+        let root = std::env::temp_dir().join("temp_test_dir_detect_cargo_workspace");
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["crates/cli/src", "crates/core/src", "tool/src/bin"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\", \"tool\"]\n",
+        )
+        .unwrap();
+        for (dir, name) in [
+            ("crates/cli", "foo-cli"),
+            ("crates/core", "foo-core"),
+            ("tool", "foo-tool"),
+        ] {
+            fs::write(
+                root.join(dir).join("Cargo.toml"),
+                format!("[package]\nname = \"{name}\"\n"),
+            )
+            .unwrap();
+        }
+        fs::write(root.join("crates/cli/src/main.rs"), "").unwrap();
+        fs::write(root.join("crates/core/src/lib.rs"), "").unwrap();
+
+        assert_eq!(
+            detect_build(&root),
+            BuildKind::Cargo {
+                cargopath: None,
+                cargoflags: vec![],
+                cargopackages: vec!["foo-cli".to_string(), "foo-tool".to_string()],
+                cargoexamples: vec![],
+                clearlocked: false,
+                cargopackagesprefixed: false,
+            }
+        );
+
+        fs::remove_file(root.join("Cargo.toml")).unwrap();
+        fs::write(root.join("CMakeLists.txt"), "").unwrap();
+        assert_eq!(detect_build(&root), BuildKind::Cmake { cmakeflags: vec![] });
+    }
+}
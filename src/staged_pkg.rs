@@ -8,6 +8,7 @@ use pkg::{Package, PackageError, PackageName};
 
 use crate::bail_other_err;
 use crate::cook::fs;
+use crate::recipe::Recipe;
 
 // This file contains code that caches recipe paths.
 
@@ -44,10 +45,62 @@ static RECIPE_PATHS: LazyLock<HashMap<PackageName, PathBuf>> = LazyLock::new(||
     recipe_paths
 });
 
+/// Virtual package names mapped to recipes that provide or replace them.
+/// This reads every recipe, so it's only built when a name is not found in [`RECIPE_PATHS`].
+static RECIPE_PROVIDERS: LazyLock<BTreeMap<String, BTreeSet<PackageName>>> = LazyLock::new(|| {
+    let mut providers: BTreeMap<String, BTreeSet<PackageName>> = BTreeMap::new();
+    for (recipe_name, recipe_dir) in RECIPE_PATHS.iter() {
+        let Ok(recipe) = Recipe::new(&recipe_dir.join("recipe.toml")) else {
+            continue;
+        };
+        for name in recipe
+            .package
+            .provides
+            .iter()
+            .chain(recipe.package.replaces.iter())
+        {
+            providers
+                .entry(name.name().to_string())
+                .or_default()
+                .insert(recipe_name.clone());
+        }
+    }
+    providers
+});
+
 pub fn find(recipe: &str) -> Option<&'static Path> {
     RECIPE_PATHS.get(recipe).map(PathBuf::as_path)
 }
 
+/// Find the recipes which provide or replace a name that has no recipe of its own
+pub fn find_providers(name: &str) -> Vec<&'static PackageName> {
+    RECIPE_PROVIDERS
+        .get(name)
+        .map(|providers| providers.iter().collect())
+        .unwrap_or_default()
+}
+
+/// Resolve a package name into its recipe name and dir, falling back to a provider
+/// if the name is virtual. Prefix and suffix of the name are kept.
+pub fn resolve(name: &PackageName) -> Result<(PackageName, &'static Path), PackageError> {
+    if let Some(dir) = find(name.name()) {
+        return Ok((name.clone(), dir));
+    }
+    let not_found = || PackageError::PackageNotFound(name.clone());
+    let provider = match find_providers(name.name())[..] {
+        [] => return Err(not_found()),
+        [provider] => provider,
+        // the dependency must name one of them
+        _ => return Err(PackageError::DependencyInvalid(name.clone())),
+    };
+    let dir = find(provider.name()).ok_or_else(not_found)?;
+    let mut resolved = provider.with_suffix(name.suffix());
+    if name.is_host() {
+        resolved = resolved.with_host();
+    }
+    Ok((resolved, dir))
+}
+
 pub fn list() -> BTreeSet<PathBuf> {
     RECIPE_PATHS.values().map(|p| p.to_path_buf()).collect()
 }
@@ -67,11 +120,12 @@ pub fn list_repo(repo_dir: impl AsRef<Path>) -> crate::Result<BTreeSet<PathBuf>>
 }
 
 pub fn new(name: &PackageName) -> Result<Package, PackageError> {
-    let dir = find(name.name()).ok_or_else(|| PackageError::PackageNotFound(name.clone()))?;
+    let (name, dir) = resolve(name)?;
     from_path(dir, name.suffix())
 }
 
-pub fn from_path(dir: &Path, feature: Option<&str>) -> Result<Package, PackageError> {
+/// Path of the stage toml of a package, which may not exist if it's not built
+pub fn stage_file(dir: &Path, feature: Option<&str>) -> PathBuf {
     let target = redoxer::target();
 
     let stage_name = match feature {
@@ -79,7 +133,11 @@ pub fn from_path(dir: &Path, feature: Option<&str>) -> Result<Package, PackageEr
         None => Cow::Borrowed("stage.toml"),
     };
 
-    let file = dir.join("target").join(target).join(stage_name.as_ref());
+    dir.join("target").join(target).join(stage_name.as_ref())
+}
+
+pub fn from_path(dir: &Path, feature: Option<&str>) -> Result<Package, PackageError> {
+    let file = stage_file(dir, feature);
     if !file.is_file() {
         return Err(PackageError::FileMissing(file));
     }
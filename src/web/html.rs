@@ -18,18 +18,45 @@ pub fn generate_html_pkg(
     let version = &package.version;
     let target = &package.target;
     let category = &get_category(&recipe.dir);
-    let description = recipe.recipe.package.description.as_deref().unwrap_or("-");
+    let description = recipe.description();
 
-    let desc_html = recipe
-        .recipe
-        .package
-        .description
-        .as_ref()
+    let desc_html = description
         .map(|desc| format!(r#"<p class="description">{}</p>"#, desc))
         .unwrap_or_default();
+    let description = description.unwrap_or("-");
 
     let repo_url = &config.repo_url;
 
+    let meta = &recipe.recipe.package;
+    let mut meta_html = String::new();
+    if let Some(license) = &meta.license {
+        meta_html += &format!("\n                <tr><th>License</th><td>{license}</td></tr>");
+    }
+    if let Some(homepage) = &meta.homepage {
+        meta_html += &format!(
+            r#"
+                <tr><th>Homepage</th><td><a href="{homepage}" target="_blank">{}</a></td></tr>"#,
+            get_hostname(homepage)
+        );
+    }
+    if let Some(upstream_url) = &meta.upstream_url {
+        meta_html += &format!(
+            r#"
+                <tr><th>Upstream</th><td><a href="{upstream_url}" target="_blank">{}</a></td></tr>"#,
+            get_hostname(upstream_url)
+        );
+    }
+    if !meta.maintainers.is_empty() {
+        meta_html += &format!(
+            "\n                <tr><th>Maintainers</th><td>{}</td></tr>",
+            meta.maintainers
+                .iter()
+                .map(|m| m.replace('<', "&lt;").replace('>', "&gt;"))
+                .collect::<Vec<_>>()
+                .join("<br>")
+        );
+    }
+
     let deps_html = if package.depends.is_empty() {
         String::from("<p>None</p>")
     } else {
@@ -174,7 +201,7 @@ pub fn generate_html_pkg(
             <table>
                 <tr><th>OS</th><td>{os}</td></tr>
                 <tr><th>Architecture</th><td>{arch}</td></tr>
-                <tr><th>Category</th><td><a href="index.html#cat-{category}">{category}</a></td></tr>
+                <tr><th>Category</th><td><a href="index.html#cat-{category}">{category}</a></td></tr>{meta_html}
                 <tr><th>Network Size</th><td>{network_size}</td></tr>
                 <tr><th>Storage Size</th><td>{storage_size}</td></tr>
                 <tr><th>File count</th><td>{files_count}</td></tr>
@@ -208,12 +235,16 @@ pub fn generate_html_index(
     for (category, pkgs) in grouped_packages {
         let cards_html: Vec<String> = pkgs
             .iter()
-            .map(|(pkg, _recipe)| {
+            .map(|(pkg, recipe)| {
                 let name = &pkg.name;
+                let desc_html = recipe
+                    .description()
+                    .map(|desc| format!("\n    <p class=\"description\">{}</p>", desc))
+                    .unwrap_or_default();
                 format!(
                     r#"
 <div class="package-card">
-    <h3 class="pkg-name"><a href="{name}.html">{name}</a></h3>
+    <h3 class="pkg-name"><a href="{name}.html">{name}</a></h3>{desc_html}
     <div class="pkg-stats">
         <span class="pkg-version">{version}</span>
         <span class="pkg-size">{size}</span>